@@ -1,13 +1,20 @@
-use std::collections::HashMap;
-use crate::database::{Database, Table};
-use crate::domain::{DatabaseKey, Record, Value, DataType};
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::ops::{Bound, RangeBounds};
+use crate::database::{Database, InsertOutcome, Table};
+use crate::domain::{ColumnDef, DatabaseKey, IndexKey, Record, Value, DataType};
 use crate::error::{DbResult, DbError};
+use crate::expression::Expr;
+use crate::functions::UserFunctions;
+use crate::results::{QueryResult, ResultSet};
 
 pub trait Command {
-    fn execute(&mut self) -> DbResult<Option<String>>;
+    fn execute(&mut self) -> DbResult<QueryResult>;
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Operator {
     Equal,
     NotEqual,
@@ -17,81 +24,1357 @@ pub enum Operator {
     LessThanOrEqual,
 }
 
-#[derive(Debug)]
-pub struct Condition {
-    pub column: String,
-    pub operator: Operator,
-    pub value: Value,
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Operator::Equal => "=",
+            Operator::NotEqual => "!=",
+            Operator::GreaterThan => ">",
+            Operator::GreaterThanOrEqual => ">=",
+            Operator::LessThan => "<",
+            Operator::LessThanOrEqual => "<=",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Comparison {
+        column: String,
+        operator: Operator,
+        value: Value,
+    },
+    AggregateComparison {
+        aggregate: Aggregate,
+        operator: Operator,
+        value: Value,
+    },
+    /// A comparison of two expressions, such as `rating * 2 > pages / 100`.
+    Compare {
+        left: Box<Expr>,
+        operator: Operator,
+        right: Box<Expr>,
+    },
+    /// `left LIKE pattern`, where `%` in the pattern matches any run of characters and `_`
+    /// any single character; ILIKE ignores case.
+    Like {
+        left: Box<Expr>,
+        pattern: Box<Expr>,
+        case_insensitive: bool,
+    },
+    In {
+        left: Box<Expr>,
+        list: Vec<Expr>,
+    },
+    /// `left BETWEEN low AND high`, with both bounds included.
+    Between {
+        left: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+    },
+    IsNull {
+        column: String,
+        negated: bool,
+    },
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    /// Whether the record satisfies the condition; UNKNOWN counts as not satisfied.
+    pub fn evaluate(&self, record: &Record) -> DbResult<bool> {
+        Ok(self.truth(record)? == Some(true))
+    }
+
+    /// Three-valued result of the condition, with `None` standing for UNKNOWN, which is
+    /// what any comparison with NULL yields.
+    fn truth(&self, record: &Record) -> DbResult<Option<bool>> {
+        match self {
+            Condition::Comparison { column, operator, value } => {
+                let field = record.fields.get(column)
+                    .ok_or(DbError::ColumnNotFound(column.clone()))?;
+                Ok(evaluate_condition(field, value, operator))
+            }
+            Condition::AggregateComparison { aggregate, operator, value } => {
+                // Grouped rows carry every computed aggregate under its display name.
+                let name = aggregate.to_string();
+                let field = record.fields.get(&name)
+                    .ok_or(DbError::ColumnNotFound(name))?;
+                Ok(evaluate_condition(field, value, operator))
+            }
+            Condition::Compare { left, operator, right } => {
+                Ok(evaluate_condition(&left.eval(record)?, &right.eval(record)?, operator))
+            }
+            Condition::Like { left, pattern, case_insensitive } => match (left.eval(record)?, pattern.eval(record)?) {
+                (Value::String(text), Value::String(pattern)) if *case_insensitive => {
+                    Ok(Some(like_matches(&text.to_lowercase(), &pattern.to_lowercase())))
+                }
+                (Value::String(text), Value::String(pattern)) => Ok(Some(like_matches(&text, &pattern))),
+                (Value::Null, _) | (_, Value::Null) => Ok(None),
+                (text, pattern) => Err(DbError::TypeMismatch(format!("Cannot match {} against {}", text, pattern))),
+            },
+            Condition::In { left, list } => {
+                // Without a match the result is UNKNOWN if the list holds a NULL, as for `x = a OR x = b`.
+                let value = left.eval(record)?;
+                let mut truth = Some(false);
+                for item in list {
+                    match evaluate_condition(&value, &item.eval(record)?, &Operator::Equal) {
+                        Some(true) => return Ok(Some(true)),
+                        Some(false) => {}
+                        None => truth = None,
+                    }
+                }
+                Ok(truth)
+            }
+            Condition::Between { left, low, high } => {
+                let value = left.eval(record)?;
+                let above = evaluate_condition(&value, &low.eval(record)?, &Operator::GreaterThanOrEqual);
+                let below = evaluate_condition(&value, &high.eval(record)?, &Operator::LessThanOrEqual);
+                Ok(match (above, below) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                })
+            }
+            Condition::IsNull { column, negated } => {
+                let field = record.fields.get(column)
+                    .ok_or(DbError::ColumnNotFound(column.clone()))?;
+                Ok(Some((*field == Value::Null) != *negated))
+            }
+            Condition::And(left, right) => match left.truth(record)? {
+                Some(false) => Ok(Some(false)),
+                left => Ok(match (left, right.truth(record)?) {
+                    (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                }),
+            },
+            Condition::Or(left, right) => match left.truth(record)? {
+                Some(true) => Ok(Some(true)),
+                left => Ok(match (left, right.truth(record)?) {
+                    (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                }),
+            },
+            Condition::Not(inner) => Ok(inner.truth(record)?.map(|value| !value)),
+        }
+    }
+
+    pub fn aggregates(&self) -> Vec<&Aggregate> {
+        match self {
+            Condition::Comparison { .. } | Condition::IsNull { .. } => Vec::new(),
+            Condition::AggregateComparison { aggregate, .. } => vec![aggregate],
+            Condition::Compare { left, right, .. } | Condition::Like { left, pattern: right, .. } => {
+                let mut aggregates = left.aggregates();
+                aggregates.extend(right.aggregates());
+                aggregates
+            }
+            Condition::In { left, list } => {
+                let mut aggregates = left.aggregates();
+                aggregates.extend(list.iter().flat_map(|item| item.aggregates()));
+                aggregates
+            }
+            Condition::Between { left, low, high } => {
+                let mut aggregates = left.aggregates();
+                aggregates.extend(low.aggregates());
+                aggregates.extend(high.aggregates());
+                aggregates
+            }
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                let mut aggregates = left.aggregates();
+                aggregates.extend(right.aggregates());
+                aggregates
+            }
+            Condition::Not(inner) => inner.aggregates(),
+        }
+    }
+
+    /// Columns the condition reads outside of aggregates.
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            Condition::Comparison { column, .. } | Condition::IsNull { column, .. } => vec![column],
+            Condition::AggregateComparison { .. } => Vec::new(),
+            Condition::Compare { left, right, .. } | Condition::Like { left, pattern: right, .. } => {
+                let mut columns = left.columns();
+                columns.extend(right.columns());
+                columns
+            }
+            Condition::In { left, list } => {
+                let mut columns = left.columns();
+                columns.extend(list.iter().flat_map(|item| item.columns()));
+                columns
+            }
+            Condition::Between { left, low, high } => {
+                let mut columns = left.columns();
+                columns.extend(low.columns());
+                columns.extend(high.columns());
+                columns
+            }
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                let mut columns = left.columns();
+                columns.extend(right.columns());
+                columns
+            }
+            Condition::Not(inner) => inner.columns(),
+        }
+    }
+
+    /// Binds calls of user-defined functions in every expression of the condition, see [`Expr::bind`].
+    pub fn bind(&mut self, functions: &UserFunctions) {
+        match self {
+            Condition::Compare { left, right, .. } | Condition::Like { left, pattern: right, .. } => {
+                left.bind(functions);
+                right.bind(functions);
+            }
+            Condition::In { left, list } => {
+                left.bind(functions);
+                list.iter_mut().for_each(|item| item.bind(functions));
+            }
+            Condition::Between { left, low, high } => {
+                left.bind(functions);
+                low.bind(functions);
+                high.bind(functions);
+            }
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                left.bind(functions);
+                right.bind(functions);
+            }
+            Condition::Not(inner) => inner.bind(functions),
+            Condition::Comparison { .. } | Condition::AggregateComparison { .. } | Condition::IsNull { .. } => {}
+        }
+    }
+
+    /// Type checks the expressions of the condition before any row is read, so that
+    /// e.g. comparing a number with a string fails even on an empty table.
+    pub fn check(&self, columns: &HashMap<String, DataType>) -> DbResult<()> {
+        match self {
+            Condition::Comparison { column, value, .. } => {
+                check_comparable(&Expr::Column(column.clone()), &Expr::Literal(value.clone()), columns)
+            }
+            Condition::AggregateComparison { aggregate, value, .. } => {
+                check_comparable(&Expr::Aggregate(aggregate.clone()), &Expr::Literal(value.clone()), columns)
+            }
+            Condition::Compare { left, right, .. } => check_comparable(left, right, columns),
+            Condition::Like { left, pattern, .. } => {
+                for expr in [left, pattern] {
+                    if let Some(data_type) = expr.data_type(columns)?
+                        && data_type != DataType::String {
+                        return Err(DbError::TypeMismatch(format!("LIKE requires a String, {} is {}", expr, data_type)));
+                    }
+                }
+                Ok(())
+            }
+            Condition::In { left, list } => list.iter().try_for_each(|item| check_comparable(left, item, columns)),
+            Condition::Between { left, low, high } => {
+                check_comparable(left, low, columns)?;
+                check_comparable(left, high, columns)
+            }
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                left.check(columns)?;
+                right.check(columns)
+            }
+            Condition::Not(inner) => inner.check(columns),
+            Condition::IsNull { .. } => Ok(()),
+        }
+    }
+}
+
+/// Writes the condition back in query syntax, as it appears in the header of a CASE column.
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Comparison { column, operator, value } => {
+                write!(f, "{} {} {}", column, operator, Expr::Literal(value.clone()))
+            }
+            Condition::AggregateComparison { aggregate, operator, value } => {
+                write!(f, "{} {} {}", aggregate, operator, Expr::Literal(value.clone()))
+            }
+            Condition::Compare { left, operator, right } => write!(f, "{} {} {}", left, operator, right),
+            Condition::Like { left, pattern, case_insensitive } => {
+                write!(f, "{} {} {}", left, if *case_insensitive { "ILIKE" } else { "LIKE" }, pattern)
+            }
+            Condition::In { left, list } => {
+                let list: Vec<String> = list.iter().map(|item| item.to_string()).collect();
+                write!(f, "{} IN ({})", left, list.join(", "))
+            }
+            Condition::Between { left, low, high } => write!(f, "{} BETWEEN {} AND {}", left, low, high),
+            Condition::IsNull { column, negated } => {
+                write!(f, "{} IS {}NULL", column, if *negated { "NOT " } else { "" })
+            }
+            Condition::And(left, right) => {
+                for (position, operand) in [left, right].into_iter().enumerate() {
+                    if position > 0 {
+                        write!(f, " AND ")?;
+                    }
+                    match **operand {
+                        Condition::Or(..) => write!(f, "({})", operand)?,
+                        _ => write!(f, "{}", operand)?,
+                    }
+                }
+                Ok(())
+            }
+            Condition::Or(left, right) => write!(f, "{} OR {}", left, right),
+            Condition::Not(inner) => match **inner {
+                Condition::And(..) | Condition::Or(..) => write!(f, "NOT ({})", inner),
+                _ => write!(f, "NOT {}", inner),
+            },
+        }
+    }
+}
+
+fn is_numeric(data_type: &DataType) -> bool {
+    matches!(data_type, DataType::Int | DataType::Float)
+}
+
+fn check_comparable(left: &Expr, right: &Expr, columns: &HashMap<String, DataType>) -> DbResult<()> {
+    match (left.data_type(columns)?, right.data_type(columns)?) {
+        (Some(a), Some(b)) if a != b && !(is_numeric(&a) && is_numeric(&b)) => {
+            Err(DbError::TypeMismatch(format!("Cannot compare {} ({}) with {} ({})", left, a, right, b)))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+/// An aggregate call such as `COUNT(*)` or `AVG(pages)`; `column` is `None` only for `COUNT(*)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aggregate {
+    pub function: AggregateFunction,
+    pub column: Option<String>,
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.function {
+            AggregateFunction::Count => "COUNT",
+            AggregateFunction::Sum => "SUM",
+            AggregateFunction::Avg => "AVG",
+            AggregateFunction::Min => "MIN",
+            AggregateFunction::Max => "MAX",
+        };
+        write!(f, "{}({})", name, self.column.as_deref().unwrap_or("*"))
+    }
+}
+
+impl Aggregate {
+    pub(crate) fn check(&self, columns: &HashMap<String, DataType>) -> DbResult<()> {
+        let Some(column) = &self.column else {
+            return Ok(());
+        };
+        let col_type = columns.get(column)
+            .ok_or_else(|| DbError::ColumnNotFound(column.clone()))?;
+        if !is_numeric(col_type) && matches!(self.function, AggregateFunction::Sum | AggregateFunction::Avg) {
+            return Err(DbError::TypeMismatch(format!("{} requires a numeric column", self)));
+        }
+        Ok(())
+    }
+
+    /// Type of the result of a checked aggregate: it follows the argument, except that
+    /// COUNT is always Int and AVG is always Float.
+    pub(crate) fn data_type(&self, columns: &HashMap<String, DataType>) -> DataType {
+        match (self.function, &self.column) {
+            (AggregateFunction::Count, _) | (_, None) => DataType::Int,
+            (AggregateFunction::Avg, _) => DataType::Float,
+            (_, Some(column)) => columns.get(column).cloned().unwrap_or(DataType::String),
+        }
+    }
+
+    fn compute(&self, records: &[&Record]) -> DbResult<Value> {
+        let Some(column) = &self.column else {
+            return Ok(Value::Int(records.len() as i64));
+        };
+        let values: Vec<&Value> = records.iter()
+            .filter_map(|r| r.fields.get(column))
+            .filter(|v| **v != Value::Null)
+            .collect();
+
+        match self.function {
+            AggregateFunction::Count => Ok(Value::Int(values.len() as i64)),
+            AggregateFunction::Sum => self.sum(&values),
+            AggregateFunction::Avg => {
+                if values.is_empty() {
+                    return Ok(Value::Null);
+                }
+                // Added up as Float, so that the average of large Int values cannot overflow.
+                let mut total = 0.0;
+                for value in &values {
+                    total += match value {
+                        Value::Int(i) => *i as f64,
+                        Value::Float(f) => *f,
+                        _ => return Err(DbError::TypeMismatch(format!("{} requires a numeric column", self))),
+                    };
+                }
+                Ok(Value::Float(total / values.len() as f64))
+            }
+            AggregateFunction::Min => Ok(values.into_iter()
+                .min_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+                .cloned().unwrap_or(Value::Null)),
+            AggregateFunction::Max => Ok(values.into_iter()
+                .max_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+                .cloned().unwrap_or(Value::Null)),
+        }
+    }
+
+    fn sum(&self, values: &[&Value]) -> DbResult<Value> {
+        let mut total = Value::Null;
+        for value in values {
+            total = match (total, value) {
+                (Value::Null, Value::Int(i)) => Value::Int(*i),
+                (Value::Null, Value::Float(f)) => Value::Float(*f),
+                (Value::Int(sum), Value::Int(i)) => Value::Int(sum.checked_add(*i)
+                    .ok_or_else(|| DbError::Overflow(self.to_string()))?),
+                (Value::Float(sum), Value::Float(f)) => Value::Float(sum + f),
+                _ => return Err(DbError::TypeMismatch(format!("{} requires a numeric column", self))),
+            };
+        }
+        Ok(total)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectItem {
+    Column(String),
+    Aggregate(Aggregate),
+    Expression(Expr),
+    Wildcard,
+}
+
+impl fmt::Display for SelectItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelectItem::Column(column) => write!(f, "{}", column),
+            SelectItem::Aggregate(aggregate) => write!(f, "{}", aggregate),
+            SelectItem::Expression(expr) => write!(f, "{}", expr),
+            SelectItem::Wildcard => write!(f, "*"),
+        }
+    }
+}
+
+impl From<&str> for SelectItem {
+    fn from(column: &str) -> Self {
+        SelectItem::Column(column.to_string())
+    }
+}
+
+/// An entry of the select list, optionally renamed with `AS`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectField {
+    pub item: SelectItem,
+    pub alias: Option<String>,
+}
+
+impl SelectField {
+    /// The header under which the field is shown.
+    pub fn name(&self) -> String {
+        self.alias.clone().unwrap_or_else(|| self.item.to_string())
+    }
+}
+
+impl From<SelectItem> for SelectField {
+    fn from(item: SelectItem) -> Self {
+        SelectField { item, alias: None }
+    }
+}
+
+impl From<&str> for SelectField {
+    fn from(column: &str) -> Self {
+        SelectItem::from(column).into()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+}
+
+/// A `JOIN table ON left_column = right_column` clause; `right_column` refers to the joined table.
+#[derive(Debug, PartialEq)]
+pub struct Join {
+    pub kind: JoinKind,
+    pub table: String,
+    pub left_column: String,
+    pub right_column: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
 }
 
 pub struct SelectCommand<'a, K: DatabaseKey> {
     pub condition: Option<Condition>,
     pub table: &'a Table<K>,
-    pub fields: Vec<String>,
+    pub joins: Vec<(Join, &'a Table<K>)>,
+    pub fields: Vec<SelectField>,
+    pub group_by: Vec<String>,
+    pub having: Option<Condition>,
+    pub order_by: Vec<(SelectItem, SortOrder)>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
 }
 
 impl<'a, K: DatabaseKey> Command for SelectCommand<'a, K> {
-    fn execute(&mut self) -> DbResult<Option<String>> {
-        let mut rows = Vec::new();
-        for record in self.table.scan() {
-            if let Some(condition) = &self.condition {
-                let value = record.fields.get(&condition.column)
-                    .ok_or(DbError::ColumnNotFound(condition.column.clone()))?;
-                if !evaluate_condition(value, &condition.value, &condition.operator) {
+    fn execute(&mut self) -> DbResult<QueryResult> {
+        self.expand_wildcards();
+        self.resolve_aliases();
+        let grouped = self.is_grouped();
+        let columns = self.columns();
+        self.check_items(&columns, grouped)?;
+
+        let offset = self.offset.unwrap_or(0);
+        let limit = self.limit.unwrap_or(usize::MAX);
+        // Without ORDER BY, grouping or joins rows come out in key order, so the scan
+        // can stop as soon as every row of the requested page has been found.
+        let needed = if self.order_by.is_empty() && !grouped && self.joins.is_empty() {
+            offset.saturating_add(limit)
+        } else {
+            usize::MAX
+        };
+
+        let joined = self.join_rows()?;
+        let access = self.condition.as_ref().and_then(|condition| access_path(condition, self.table));
+        let records = match (&joined, access) {
+            (Some(rows), _) => self.filter(rows.iter(), needed)?,
+            (None, Some(AccessPath::Keys(keys))) => {
+                self.filter(keys.iter().filter_map(|key| self.table.store.get(key)), needed)?
+            }
+            (None, Some(AccessPath::Range(lower, upper))) => self.filter(self.table.range(lower, upper), needed)?,
+            (None, None) => self.filter(self.table.scan(), needed)?,
+        };
+
+        if grouped {
+            let groups = self.group_records(records)?;
+            self.output(groups, &columns, offset, limit)
+        } else {
+            self.output(records, &columns, offset, limit)
+        }
+    }
+}
+
+impl<'a, K: DatabaseKey> SelectCommand<'a, K> {
+    /// Replaces `*` with every column in declaration order, qualified by table when joining.
+    fn expand_wildcards(&mut self) {
+        if !self.fields.iter().any(|field| field.item == SelectItem::Wildcard) {
+            return;
+        }
+        let tables = self.tables();
+        let qualify = !self.joins.is_empty();
+        let mut fields = Vec::new();
+        for field in self.fields.drain(..) {
+            if field.item != SelectItem::Wildcard {
+                fields.push(field);
+                continue;
+            }
+            for table in &tables {
+                for column in table.columns() {
+                    let name = if qualify { format!("{}.{}", table.name, column) } else { column.clone() };
+                    fields.push(SelectItem::Column(name).into());
+                }
+            }
+        }
+        self.fields = fields;
+    }
+
+    /// Lets ORDER BY refer to select list entries by their alias.
+    fn resolve_aliases(&mut self) {
+        for (item, _) in &mut self.order_by {
+            if let SelectItem::Column(name) = item
+                && let Some(field) = self.fields.iter().find(|f| f.alias.as_ref() == Some(name)) {
+                *item = field.item.clone();
+            }
+        }
+    }
+
+    fn items(&self) -> impl Iterator<Item = &SelectItem> {
+        self.fields.iter().map(|field| &field.item)
+            .chain(self.order_by.iter().map(|(item, _)| item))
+    }
+
+    fn is_grouped(&self) -> bool {
+        !self.group_by.is_empty()
+            || self.having.is_some()
+            || self.items().any(|item| match item {
+                SelectItem::Aggregate(_) => true,
+                SelectItem::Expression(expr) => !expr.aggregates().is_empty(),
+                SelectItem::Column(_) | SelectItem::Wildcard => false,
+            })
+    }
+
+    fn aggregates(&self) -> Vec<&Aggregate> {
+        let mut aggregates = Vec::new();
+        let having = self.having.iter().flat_map(|h| h.aggregates());
+        for aggregate in self.items().flat_map(|item| match item {
+            SelectItem::Aggregate(aggregate) => vec![aggregate],
+            SelectItem::Expression(expr) => expr.aggregates(),
+            SelectItem::Column(_) | SelectItem::Wildcard => Vec::new(),
+        }).chain(having) {
+            if !aggregates.contains(&aggregate) {
+                aggregates.push(aggregate);
+            }
+        }
+        aggregates
+    }
+
+    /// Columns that rows of this query expose. With joins every column is available as
+    /// `table.column`, and also by its bare name when no other joined table has it.
+    fn columns(&self) -> HashMap<String, DataType> {
+        if self.joins.is_empty() {
+            return self.table.schema().clone();
+        }
+        let tables = self.tables();
+        let shared = shared_columns(&tables);
+        let mut columns = HashMap::new();
+        for table in tables {
+            for (column, col_type) in table.schema() {
+                columns.insert(format!("{}.{}", table.name, column), col_type.clone());
+                if !shared.contains(column.as_str()) {
+                    columns.insert(column.clone(), col_type.clone());
+                }
+            }
+        }
+        columns
+    }
+
+    fn tables(&self) -> Vec<&'a Table<K>> {
+        std::iter::once(self.table).chain(self.joins.iter().map(|(_, table)| *table)).collect()
+    }
+
+    fn check_items(&self, columns: &HashMap<String, DataType>, grouped: bool) -> DbResult<()> {
+        let ungrouped = |column: &str| DbError::InvalidCommand(format!(
+            "Column '{}' must appear in GROUP BY or be used in an aggregate", column
+        ));
+        for (join, right) in &self.joins {
+            if !columns.contains_key(&join.left_column) {
+                return Err(DbError::ColumnNotFound(join.left_column.clone()));
+            }
+            if !right.has_column(join_column(join)) {
+                return Err(DbError::ColumnNotFound(join.right_column.clone()));
+            }
+        }
+        for column in &self.group_by {
+            if !columns.contains_key(column) {
+                return Err(DbError::ColumnNotFound(column.clone()));
+            }
+        }
+        for aggregate in self.aggregates() {
+            aggregate.check(columns)?;
+        }
+        for item in self.items() {
+            let referenced = match item {
+                SelectItem::Column(column) => vec![column.as_str()],
+                SelectItem::Expression(expr) => {
+                    expr.data_type(columns)?;
+                    expr.columns()
+                }
+                SelectItem::Aggregate(_) | SelectItem::Wildcard => Vec::new(),
+            };
+            for column in referenced {
+                if !columns.contains_key(column) {
+                    return Err(DbError::ColumnNotFound(column.to_string()));
+                }
+                if grouped && !self.group_by.iter().any(|c| c == column) {
+                    return Err(ungrouped(column));
+                }
+            }
+        }
+        for condition in self.condition.iter().chain(&self.having) {
+            condition.check(columns)?;
+        }
+        for column in self.having.iter().flat_map(|having| having.columns()) {
+            if !self.group_by.iter().any(|c| c == column) {
+                return Err(ungrouped(column));
+            }
+        }
+        Ok(())
+    }
+
+    fn filter<'r>(&self, records: impl Iterator<Item = &'r Record>, needed: usize) -> DbResult<Vec<&'r Record>> {
+        let mut result = Vec::new();
+        for record in records {
+            if result.len() >= needed {
+                break;
+            }
+            if let Some(condition) = &self.condition
+                && !condition.evaluate(record)? {
+                continue;
+            }
+            result.push(record);
+        }
+        Ok(result)
+    }
+
+    /// Builds the combined rows of all joined tables, or `None` when the query has no joins.
+    /// A join on the right table's primary key is answered by a key lookup instead of a scan.
+    fn join_rows(&self) -> DbResult<Option<Vec<Record>>> {
+        if self.joins.is_empty() {
+            return Ok(None);
+        }
+        let shared = shared_columns(&self.tables());
+        let mut rows: Vec<Record> = self.table.scan()
+            .map(|record| {
+                let mut fields = HashMap::new();
+                add_qualified(&mut fields, &self.table.name, &record.fields, &shared);
+                Record { fields }
+            })
+            .collect();
+
+        for (join, right) in &self.joins {
+            let right_column = join_column(join);
+            let null_fields: HashMap<String, Value> = right.schema().keys()
+                .map(|column| (column.clone(), Value::Null))
+                .collect();
+
+            let mut joined = Vec::new();
+            for row in rows {
+                let matches: Vec<&Record> = match row.fields.get(&join.left_column) {
+                    None | Some(Value::Null) => Vec::new(),
+                    Some(value) if right.is_pk(right_column) => K::from_value(value)
+                        .and_then(|key| right.store.get(&key))
+                        .into_iter()
+                        .collect(),
+                    Some(value) => right.scan()
+                        .filter(|record| record.fields.get(right_column) == Some(value))
+                        .collect(),
+                };
+
+                if matches.is_empty() {
+                    if join.kind == JoinKind::Left {
+                        let mut fields = row.fields;
+                        add_qualified(&mut fields, &right.name, &null_fields, &shared);
+                        joined.push(Record { fields });
+                    }
                     continue;
                 }
+                for record in matches {
+                    let mut fields = row.fields.clone();
+                    add_qualified(&mut fields, &right.name, &record.fields, &shared);
+                    joined.push(Record { fields });
+                }
+            }
+            rows = joined;
+        }
+        Ok(Some(rows))
+    }
+
+    /// Collapses the filtered records into one record per group, holding the group
+    /// columns and every aggregate under its display name, and applies HAVING.
+    fn group_records(&self, records: Vec<&Record>) -> DbResult<Vec<Record>> {
+        let mut groups: BTreeMap<Vec<IndexKey>, Vec<&Record>> = BTreeMap::new();
+        for record in records {
+            let key = self.group_by.iter()
+                .map(|column| IndexKey(record.fields.get(column).cloned().unwrap_or(Value::Null)))
+                .collect();
+            groups.entry(key).or_default().push(record);
+        }
+        if groups.is_empty() && self.group_by.is_empty() {
+            groups.insert(Vec::new(), Vec::new());
+        }
+
+        let aggregates = self.aggregates();
+        let mut result = Vec::new();
+        for (key, members) in groups {
+            let mut fields: HashMap<String, Value> = self.group_by.iter().cloned()
+                .zip(key.into_iter().map(|IndexKey(value)| value))
+                .collect();
+            for aggregate in &aggregates {
+                fields.insert(aggregate.to_string(), aggregate.compute(&members)?);
+            }
+            let record = Record { fields };
+            if let Some(having) = &self.having
+                && !having.evaluate(&record)? {
+                continue;
+            }
+            result.push(record);
+        }
+        Ok(result)
+    }
+
+    fn output<R: Borrow<Record>>(
+        &self,
+        mut records: Vec<R>,
+        columns: &HashMap<String, DataType>,
+        offset: usize,
+        limit: usize,
+    ) -> DbResult<QueryResult> {
+        if !self.order_by.is_empty() {
+            // Sort keys are computed up front, since evaluating an expression can fail.
+            let mut keyed = Vec::with_capacity(records.len());
+            for record in records {
+                let keys = self.order_by.iter()
+                    .map(|(item, _)| item_value(item, record.borrow()))
+                    .collect::<DbResult<Vec<Value>>>()?;
+                keyed.push((keys, record));
+            }
+            keyed.sort_by(|(a, _), (b, _)| compare_keys(a, b, &self.order_by));
+            records = keyed.into_iter().map(|(_, record)| record).collect();
+        }
+
+        let names = self.fields.iter().map(|field| field.name()).collect();
+        let types = self.fields.iter().map(|field| item_type(&field.item, columns)).collect();
+        let mut result = ResultSet::new(names, types);
+        for record in records.iter().skip(offset).take(limit) {
+            let mut row = Vec::with_capacity(self.fields.len());
+            for (field, data_type) in self.fields.iter().zip(&result.types) {
+                // Entries mixing Int and Float, such as `COALESCE(rating, 0)`, are shown as Float throughout.
+                row.push(match (item_value(&field.item, record.borrow())?, data_type) {
+                    (Value::Int(n), DataType::Float) => Value::Float(n as f64),
+                    (value, _) => value,
+                });
+            }
+            result.rows.push(row);
+        }
+        Ok(QueryResult::Rows(result))
+    }
+}
+
+/// Type of a checked select list entry. An expression that can only be NULL is shown as String.
+fn item_type(item: &SelectItem, columns: &HashMap<String, DataType>) -> DataType {
+    match item {
+        SelectItem::Column(column) => columns.get(column).cloned().unwrap_or(DataType::String),
+        SelectItem::Aggregate(aggregate) => aggregate.data_type(columns),
+        SelectItem::Expression(expr) => expr.data_type(columns).ok().flatten().unwrap_or(DataType::String),
+        SelectItem::Wildcard => DataType::String,
+    }
+}
+
+/// Value of a select list or ORDER BY entry for a (possibly grouped) record.
+fn item_value(item: &SelectItem, record: &Record) -> DbResult<Value> {
+    match item {
+        SelectItem::Expression(expr) => expr.eval(record),
+        _ => {
+            let name = item.to_string();
+            record.fields.get(&name).cloned().ok_or(DbError::ColumnNotFound(name))
+        }
+    }
+}
+
+/// How the records that can satisfy a WHERE clause are found when the whole table does
+/// not have to be scanned. Either way records are visited in key order.
+enum AccessPath<K> {
+    /// Only the records stored under these keys.
+    Keys(BTreeSet<K>),
+    /// The records whose primary key lies between the bounds.
+    Range(Bound<K>, Bound<K>),
+}
+
+/// Narrows down the records that can satisfy `condition` using the primary key and the
+/// indexes of `table`; `None` if some part of the condition needs a full scan.
+fn access_path<K: DatabaseKey>(condition: &Condition, table: &Table<K>) -> Option<AccessPath<K>> {
+    match condition {
+        Condition::Comparison { column, operator, value } if table.is_pk(column) => {
+            if *value == Value::Null {
+                return Some(AccessPath::Keys(BTreeSet::new()));
+            }
+            let key = K::from_value(value)?;
+            match operator {
+                Operator::Equal => Some(AccessPath::Keys(BTreeSet::from([key]))),
+                Operator::GreaterThan => Some(AccessPath::Range(Bound::Excluded(key), Bound::Unbounded)),
+                Operator::GreaterThanOrEqual => Some(AccessPath::Range(Bound::Included(key), Bound::Unbounded)),
+                Operator::LessThan => Some(AccessPath::Range(Bound::Unbounded, Bound::Excluded(key))),
+                Operator::LessThanOrEqual => Some(AccessPath::Range(Bound::Unbounded, Bound::Included(key))),
+                Operator::NotEqual => None,
+            }
+        }
+        Condition::Comparison { column, operator, value } => {
+            let index = table.index_on(column)?;
+            // The index orders values of the column's own type, so an Int bound on a Float
+            // column is widened and any other mismatch is left to the scan.
+            match (table.schema().get(column), value) {
+                (Some(DataType::Float), Value::Int(n)) => index.lookup(operator, &Value::Float(*n as f64)),
+                (Some(data_type), value) if value.data_type().is_some_and(|t| t != *data_type) => None,
+                _ => index.lookup(operator, value),
+            }.map(AccessPath::Keys)
+        }
+        Condition::And(left, right) => match (access_path(left, table), access_path(right, table)) {
+            (Some(AccessPath::Keys(left)), Some(AccessPath::Keys(right))) => {
+                Some(AccessPath::Keys(left.intersection(&right).cloned().collect()))
+            }
+            (Some(AccessPath::Keys(keys)), Some(AccessPath::Range(lower, upper)))
+            | (Some(AccessPath::Range(lower, upper)), Some(AccessPath::Keys(keys))) => {
+                let range = (lower, upper);
+                Some(AccessPath::Keys(keys.into_iter().filter(|key| range.contains(key)).collect()))
+            }
+            (Some(AccessPath::Range(lower_a, upper_a)), Some(AccessPath::Range(lower_b, upper_b))) => {
+                Some(AccessPath::Range(max_lower(lower_a, lower_b), min_upper(upper_a, upper_b)))
+            }
+            (left, right) => left.or(right),
+        },
+        Condition::Or(left, right) => match (access_path(left, table)?, access_path(right, table)?) {
+            (AccessPath::Keys(mut left), AccessPath::Keys(right)) => {
+                left.extend(right);
+                Some(AccessPath::Keys(left))
+            }
+            _ => None,
+        },
+        Condition::In { left, list } => {
+            let Expr::Column(column) = &**left else {
+                return None;
+            };
+            let mut keys = BTreeSet::new();
+            for item in list {
+                let Expr::Literal(value) = item else {
+                    return None;
+                };
+                let equal = Condition::Comparison {
+                    column: column.clone(),
+                    operator: Operator::Equal,
+                    value: value.clone(),
+                };
+                match access_path(&equal, table)? {
+                    AccessPath::Keys(found) => keys.extend(found),
+                    AccessPath::Range(..) => return None,
+                }
             }
-            let mut row_strings = Vec::new();
-            for field in &self.fields {
-                let val = record.fields.get(field)
-                    .ok_or_else(|| DbError::ColumnNotFound(field.clone()))?;
-                row_strings.push(format!("{}", val));
+            Some(AccessPath::Keys(keys))
+        }
+        Condition::Between { left, low, high } => {
+            let (Expr::Column(column), Expr::Literal(low), Expr::Literal(high)) = (&**left, &**low, &**high) else {
+                return None;
+            };
+            let bound = |operator, value: &Value| {
+                Box::new(Condition::Comparison { column: column.clone(), operator, value: value.clone() })
+            };
+            let range = Condition::And(
+                bound(Operator::GreaterThanOrEqual, low),
+                bound(Operator::LessThanOrEqual, high),
+            );
+            access_path(&range, table)
+        }
+        Condition::AggregateComparison { .. }
+        | Condition::Compare { .. }
+        | Condition::Like { .. }
+        | Condition::IsNull { .. }
+        | Condition::Not(_) => None,
+    }
+}
+
+/// The more restrictive of two lower bounds.
+fn max_lower<K: Ord>(a: Bound<K>, b: Bound<K>) -> Bound<K> {
+    let keep_a = match (&a, &b) {
+        (_, Bound::Unbounded) => true,
+        (Bound::Unbounded, _) => false,
+        (Bound::Included(x) | Bound::Excluded(x), Bound::Included(y) | Bound::Excluded(y)) => {
+            x > y || (x == y && matches!(a, Bound::Excluded(_)))
+        }
+    };
+    if keep_a { a } else { b }
+}
+
+/// The more restrictive of two upper bounds.
+fn min_upper<K: Ord>(a: Bound<K>, b: Bound<K>) -> Bound<K> {
+    let keep_a = match (&a, &b) {
+        (_, Bound::Unbounded) => true,
+        (Bound::Unbounded, _) => false,
+        (Bound::Included(x) | Bound::Excluded(x), Bound::Included(y) | Bound::Excluded(y)) => {
+            x < y || (x == y && matches!(a, Bound::Excluded(_)))
+        }
+    };
+    if keep_a { a } else { b }
+}
+
+fn join_column(join: &Join) -> &str {
+    join.right_column.strip_prefix(&format!("{}.", join.table)).unwrap_or(&join.right_column)
+}
+
+/// Names of columns that appear in more than one of the given tables.
+fn shared_columns<K: DatabaseKey>(tables: &[&Table<K>]) -> HashSet<String> {
+    let mut seen = HashSet::new();
+    let mut shared = HashSet::new();
+    for table in tables {
+        for column in table.schema().keys() {
+            if !seen.insert(column.clone()) {
+                shared.insert(column.clone());
             }
-            rows.push(row_strings.join(", "));
         }
-        Ok(Some(rows.join("\n")))
     }
+    shared
+}
+
+fn add_qualified(
+    target: &mut HashMap<String, Value>,
+    table: &str,
+    fields: &HashMap<String, Value>,
+    shared: &HashSet<String>,
+) {
+    for (column, value) in fields {
+        target.insert(format!("{}.{}", table, column), value.clone());
+        if !shared.contains(column) {
+            target.insert(column.clone(), value.clone());
+        }
+    }
+}
+
+fn compare_keys(a: &[Value], b: &[Value], order_by: &[(SelectItem, SortOrder)]) -> Ordering {
+    for ((left, right), (_, order)) in a.iter().zip(b).zip(order_by) {
+        // NULL sorts after every other value: last in ascending and first in descending order.
+        let ordering = match (left, right) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            (Value::Null, _) => Ordering::Greater,
+            (_, Value::Null) => Ordering::Less,
+            // Entries such as `COALESCE(rating, 0)` mix Int and Float values, which are compared as numbers.
+            (Value::Int(a), Value::Float(_)) => Value::Float(*a as f64).total_cmp(right),
+            (Value::Float(_), Value::Int(b)) => left.total_cmp(&Value::Float(*b as f64)),
+            _ => left.total_cmp(right),
+        };
+        let ordering = match order {
+            SortOrder::Ascending => ordering,
+            SortOrder::Descending => ordering.reverse(),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
 }
 
-fn evaluate_condition(value1: &Value, value2: &Value, operator: &Operator) -> bool {
-    match operator {
+/// Matches `text` against a LIKE pattern. Both are compared character by character, so
+/// `_` stands for a single character even outside ASCII.
+fn like_matches(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    // Greedy matching that returns to the last `%` on a mismatch, letting it absorb one more character.
+    let (mut t, mut p) = (0, 0);
+    let mut last_wildcard: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('%') => {
+                last_wildcard = Some((p, t));
+                p += 1;
+            }
+            Some('_') => {
+                t += 1;
+                p += 1;
+            }
+            Some(c) if *c == text[t] => {
+                t += 1;
+                p += 1;
+            }
+            _ => match last_wildcard {
+                Some((wildcard, absorbed)) => {
+                    last_wildcard = Some((wildcard, absorbed + 1));
+                    p = wildcard + 1;
+                    t = absorbed + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '%')
+}
+
+/// Compares two values; `None` (UNKNOWN) if either of them is NULL.
+pub(crate) fn evaluate_condition(value1: &Value, value2: &Value, operator: &Operator) -> Option<bool> {
+    if *value1 == Value::Null || *value2 == Value::Null {
+        return None;
+    }
+    // An Int compared with a Float is widened, so that `3 = 3.0`.
+    let widened;
+    let (value1, value2) = match (value1, value2) {
+        (Value::Int(a), Value::Float(_)) => {
+            widened = Value::Float(*a as f64);
+            (&widened, value2)
+        }
+        (Value::Float(_), Value::Int(b)) => {
+            widened = Value::Float(*b as f64);
+            (value1, &widened)
+        }
+        _ => (value1, value2),
+    };
+    Some(match operator {
         Operator::Equal => value1 == value2,
         Operator::NotEqual => value1 != value2,
         Operator::GreaterThan => value1 > value2,
         Operator::GreaterThanOrEqual => value1 >= value2,
         Operator::LessThan => value1 < value2,
         Operator::LessThanOrEqual => value1 <= value2,
-    }
+    })
 }
 
 pub struct CreateTableCommand<'a, K: DatabaseKey> {
     pub database: &'a mut Database<K>,
     pub name: String,
-    pub pk_name: String,
-    pub schema: HashMap<String, DataType>,
+    pub pk_columns: Vec<String>,
+    pub columns: Vec<ColumnDef>,
 }
 
 impl<'a, K: DatabaseKey> Command for CreateTableCommand<'a, K> {
-    fn execute(&mut self) -> DbResult<Option<String>> {
+    fn execute(&mut self) -> DbResult<QueryResult> {
+        let mut names = HashSet::new();
+        for column in &self.columns {
+            if !names.insert(&column.name) {
+                return Err(DbError::InvalidCommand(format!("Duplicate column '{}'", column.name)));
+            }
+            column.check(&self.pk_columns)?;
+        }
+        if self.pk_columns.len() > 1 && !K::supports_composite() {
+            return Err(DbError::InvalidCommand("Composite primary keys need the auto key type".into()));
+        }
+        let mut key_names = HashSet::new();
+        for pk in &self.pk_columns {
+            if !key_names.insert(pk) {
+                return Err(DbError::InvalidCommand(format!("Duplicate primary key column '{}'", pk)));
+            }
+            match self.columns.iter().find(|column| column.name == *pk) {
+                None => return Err(DbError::ColumnNotFound(pk.clone())),
+                Some(column) if !K::supports(&column.data_type) => {
+                    return Err(DbError::TypeMismatch(format!(
+                        "Primary key '{}' cannot be of type {}",
+                        column.name, column.data_type
+                    )));
+                }
+                Some(_) => {}
+            }
+        }
         let table = Table::new(
             self.name.clone(),
-            self.schema.clone(),
-            self.pk_name.clone(),
+            self.columns.clone(),
+            self.pk_columns.clone(),
         );
         self.database.create_table(table)?;
-        Ok(Some(format!("Table {} created.", self.name)))
+        Ok(QueryResult::Message(format!("Table {} created.", self.name)))
+    }
+}
+
+pub struct DropTableCommand<'a, K: DatabaseKey> {
+    pub database: &'a mut Database<K>,
+    pub name: String,
+    pub if_exists: bool,
+}
+
+impl<'a, K: DatabaseKey> Command for DropTableCommand<'a, K> {
+    fn execute(&mut self) -> DbResult<QueryResult> {
+        match self.database.drop_table(&self.name) {
+            Ok(()) => Ok(QueryResult::Message(format!("Table {} dropped.", self.name))),
+            Err(DbError::TableNotFound(_)) if self.if_exists => {
+                Ok(QueryResult::Message(format!("Table {} does not exist, skipped.", self.name)))
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+pub struct TruncateCommand<'a, K: DatabaseKey> {
+    pub table: &'a mut Table<K>,
+}
+
+impl<'a, K: DatabaseKey> Command for TruncateCommand<'a, K> {
+    fn execute(&mut self) -> DbResult<QueryResult> {
+        Ok(QueryResult::RowsAffected(self.table.truncate()))
+    }
+}
+
+/// A single change made by ALTER TABLE.
+#[derive(Debug, PartialEq)]
+pub enum AlterAction {
+    AddColumn(ColumnDef),
+    DropColumn(String),
+    RenameColumn { from: String, to: String },
+    RenameTable(String),
+}
+
+pub struct AlterTableCommand<'a, K: DatabaseKey> {
+    pub database: &'a mut Database<K>,
+    pub table: String,
+    pub action: AlterAction,
+}
+
+impl<'a, K: DatabaseKey> Command for AlterTableCommand<'a, K> {
+    fn execute(&mut self) -> DbResult<QueryResult> {
+        match &self.action {
+            AlterAction::AddColumn(column) => self.database.get_table_mut(&self.table)?.add_column(column.clone())?,
+            AlterAction::DropColumn(column) => self.database.get_table_mut(&self.table)?.drop_column(column)?,
+            AlterAction::RenameColumn { from, to } => self.database.get_table_mut(&self.table)?.rename_column(from, to)?,
+            AlterAction::RenameTable(name) => self.database.rename_table(&self.table, name)?,
+        }
+        Ok(QueryResult::Message(format!("Table {} altered.", self.table)))
+    }
+}
+
+pub struct ShowTablesCommand<'a, K: DatabaseKey> {
+    pub database: &'a Database<K>,
+}
+
+impl<'a, K: DatabaseKey> Command for ShowTablesCommand<'a, K> {
+    fn execute(&mut self) -> DbResult<QueryResult> {
+        let mut result = ResultSet::new(
+            vec!["table".into(), "key".into(), "rows".into()],
+            vec![DataType::String, DataType::String, DataType::Int],
+        );
+        for name in self.database.table_names() {
+            let info = self.database.describe(name)?;
+            result.rows.push(vec![
+                Value::String(info.name),
+                Value::String(info.pk_columns.join(", ")),
+                Value::Int(info.row_count as i64),
+            ]);
+        }
+        Ok(QueryResult::Rows(result))
     }
 }
 
+/// Lists the columns of a table, one row per column with its type, constraints and the
+/// indexes built on it.
+pub struct DescribeCommand<'a, K: DatabaseKey> {
+    pub table: &'a Table<K>,
+}
+
+impl<'a, K: DatabaseKey> Command for DescribeCommand<'a, K> {
+    fn execute(&mut self) -> DbResult<QueryResult> {
+        let info = self.table.info();
+        let mut result = ResultSet::new(
+            ["column", "type", "nullable", "default", "key", "indexes"].map(String::from).to_vec(),
+            vec![DataType::String, DataType::String, DataType::Bool, DataType::String, DataType::String, DataType::String],
+        );
+        for column in info.columns {
+            let key = match (info.pk_columns.contains(&column.name), column.auto_increment) {
+                (true, true) => Value::String("PRIMARY AUTO_INCREMENT".into()),
+                (true, false) => Value::String("PRIMARY".into()),
+                (false, _) => Value::Null,
+            };
+            let indexes: Vec<&str> = info.indexes.iter()
+                .filter(|index| index.column == column.name)
+                .map(|index| index.name.as_str())
+                .collect();
+            result.rows.push(vec![
+                Value::String(column.name),
+                Value::String(column.data_type.to_string()),
+                Value::Bool(column.nullable),
+                column.default.map_or(Value::Null, |value| Value::String(value.to_string())),
+                key,
+                if indexes.is_empty() { Value::Null } else { Value::String(indexes.join(" ")) },
+            ]);
+        }
+        Ok(QueryResult::Rows(result))
+    }
+}
+
+/// How INSERT treats a record whose primary key is already taken.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OnConflict {
+    /// Fail with [`DbError::DuplicateKey`].
+    Fail,
+    /// `ON CONFLICT DO NOTHING`: keep the stored record.
+    DoNothing,
+    /// `ON CONFLICT DO UPDATE SET ...`: apply the assignments to the stored record.
+    DoUpdate(Vec<(String, Value)>),
+    /// `REPLACE`: overwrite the stored record.
+    Replace,
+}
+
+/// Inserts all records or, if any of them is invalid or duplicates a key it cannot resolve,
+/// none. Plain inserts report a row count; upserts list every key with what happened to it.
 pub struct InsertCommand<'a, K: DatabaseKey> {
     pub table: &'a mut Table<K>,
-    pub record: Record,
+    pub records: Vec<Record>,
+    pub on_conflict: OnConflict,
+}
+
+impl<'a, K: DatabaseKey> Command for InsertCommand<'a, K> {
+    fn execute(&mut self) -> DbResult<QueryResult> {
+        if self.on_conflict == OnConflict::Fail {
+            let count = self.table.insert_all(self.records.clone())?;
+            return Ok(QueryResult::RowsAffected(count));
+        }
+
+        let outcomes = self.table.upsert_all(self.records.clone(), &self.on_conflict)?;
+        let pk_columns = self.table.pk_columns();
+        let mut columns = pk_columns.to_vec();
+        columns.push("action".into());
+        let mut types: Vec<DataType> = pk_columns.iter()
+            .map(|pk| self.table.column_type(pk).cloned().unwrap_or(DataType::String))
+            .collect();
+        types.push(DataType::String);
+
+        let mut result = ResultSet::new(columns, types);
+        for (key, outcome) in outcomes {
+            // A skipped record shares its key with the stored one.
+            let record = self.table.store.get(&key);
+            let mut row: Vec<Value> = pk_columns.iter()
+                .map(|pk| record.and_then(|record| record.fields.get(pk)).cloned().unwrap_or(Value::Null))
+                .collect();
+            let action = match outcome {
+                InsertOutcome::Inserted => "inserted",
+                InsertOutcome::Updated => "updated",
+                InsertOutcome::Skipped => "skipped",
+            };
+            row.push(Value::String(action.into()));
+            result.rows.push(row);
+        }
+        Ok(QueryResult::Rows(result))
+    }
+}
+
+pub struct UpdateCommand<'a, K: DatabaseKey> {
+    pub table: &'a mut Table<K>,
+    pub values: Vec<(String, Value)>,
+    pub condition: Option<Condition>,
+}
+
+impl<'a, K: DatabaseKey> Command for UpdateCommand<'a, K> {
+    fn execute(&mut self) -> DbResult<QueryResult> {
+        for (column, _) in &self.values {
+            if !self.table.has_column(column) {
+                return Err(DbError::ColumnNotFound(column.clone()));
+            }
+        }
+        if let Some(condition) = &self.condition {
+            condition.check(self.table.schema())?;
+        }
+
+        let mut changes = Vec::new();
+        for (key, record) in &self.table.store {
+            if let Some(condition) = &self.condition
+                && !condition.evaluate(record)? {
+                continue;
+            }
+            let mut updated = record.clone();
+            for (column, value) in &self.values {
+                updated.fields.insert(column.clone(), value.clone());
+            }
+            changes.push((key.clone(), updated));
+        }
+
+        let count = self.table.update(changes)?;
+        Ok(QueryResult::RowsAffected(count))
+    }
+}
+
+pub struct CreateIndexCommand<'a, K: DatabaseKey> {
+    pub database: &'a mut Database<K>,
+    pub name: String,
+    pub table: String,
+    pub column: String,
+}
+
+impl<'a, K: DatabaseKey> Command for CreateIndexCommand<'a, K> {
+    fn execute(&mut self) -> DbResult<QueryResult> {
+        self.database.create_index(self.name.clone(), &self.table, self.column.clone())?;
+        Ok(QueryResult::Message(format!("Index {} created.", self.name)))
+    }
+}
+
+pub struct DropIndexCommand<'a, K: DatabaseKey> {
+    pub database: &'a mut Database<K>,
+    pub name: String,
 }
 
-impl<'a, K: DatabaseKey> Command for InsertCommand<'a, K> {
-    fn execute(&mut self) -> DbResult<Option<String>> {
-        self.table.insert(self.record.clone())?;
-        Ok(Some("Record inserted".to_string()))
+impl<'a, K: DatabaseKey> Command for DropIndexCommand<'a, K> {
+    fn execute(&mut self) -> DbResult<QueryResult> {
+        self.database.drop_index(&self.name)?;
+        Ok(QueryResult::Message(format!("Index {} dropped.", self.name)))
     }
 }
 
@@ -101,14 +1384,42 @@ pub struct DeleteCommand<'a, K: DatabaseKey> {
 }
 
 impl<'a, K: DatabaseKey> Command for DeleteCommand<'a, K> {
-    fn execute(&mut self) -> DbResult<Option<String>> {
+    fn execute(&mut self) -> DbResult<QueryResult> {
         match self.table.delete(&self.key) {
-            Some(_) => Ok(Some("Deleted record".to_string())),
+            Some(_) => Ok(QueryResult::RowsAffected(1)),
             None => Err(DbError::KeyMismatch)
         }
     }
 }
 
+/// Removes every record matching the condition. The matching keys are collected before
+/// anything is removed, so the store is never modified while it is being iterated.
+pub struct DeleteWhereCommand<'a, K: DatabaseKey> {
+    pub table: &'a mut Table<K>,
+    pub condition: Option<Condition>,
+}
+
+impl<'a, K: DatabaseKey> Command for DeleteWhereCommand<'a, K> {
+    fn execute(&mut self) -> DbResult<QueryResult> {
+        if let Some(condition) = &self.condition {
+            condition.check(self.table.schema())?;
+        }
+        let mut keys = Vec::new();
+        for (key, record) in &self.table.store {
+            if let Some(condition) = &self.condition
+                && !condition.evaluate(record)? {
+                continue;
+            }
+            keys.push(key.clone());
+        }
+
+        for key in &keys {
+            self.table.delete(key);
+        }
+        Ok(QueryResult::RowsAffected(keys.len()))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -117,19 +1428,44 @@ mod tests {
         Database::new()
     }
 
-    fn get_people_schema() -> HashMap<String, DataType> {
-        let mut s = HashMap::new();
-        s.insert("id".into(), DataType::Int);
-        s.insert("job".into(), DataType::String);
-        s.insert("height".into(), DataType::Float);
-        s.insert("age".into(), DataType::Int);
-        s.insert("sex".into(), DataType::String);
-        s
+    fn render(result: &ResultSet) -> String {
+        let mut lines = vec![result.columns.join(", ")];
+        for row in &result.rows {
+            let values: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+            lines.push(values.join(", "));
+        }
+        lines.join("\n")
+    }
+
+    /// A SELECT of `fields` from `table` without any other clause; tests fill in the rest
+    /// with struct update syntax.
+    fn select(table: &Table<i64>, fields: Vec<SelectField>) -> SelectCommand<'_, i64> {
+        SelectCommand {
+            table,
+            joins: Vec::new(),
+            fields,
+            condition: None,
+            group_by: Vec::new(),
+            having: None,
+            order_by: Vec::new(),
+            limit: None,
+            offset: None,
+        }
+    }
+
+    fn get_people_schema() -> Vec<(String, DataType)> {
+        vec![
+            ("id".into(), DataType::Int),
+            ("job".into(), DataType::String),
+            ("height".into(), DataType::Float),
+            ("age".into(), DataType::Int),
+            ("sex".into(), DataType::String),
+        ]
     }
 
     #[test]
     fn test_exec_select() {
-        let mut t = Table::new("people".into(), get_people_schema(),"id".into());
+        let mut t = Table::new("people".into(), get_people_schema(), vec!["id".into()]);
 
         let mut f1 = HashMap::new();
         f1.insert("id".into(), Value::Int(1));
@@ -147,43 +1483,484 @@ mod tests {
         f2.insert("age".into(), Value::Int(25));
         t.store.insert(2, Record { fields: f2 });
 
-        let cond = Condition {
+        let cond = Condition::Comparison {
             column: "sex".into(),
             operator: Operator::Equal,
             value: Value::String("male".into()),
         };
 
+        let mut cmd = SelectCommand { condition: Some(cond), ..select(&t, vec!["job".into()]) };
+
+        match cmd.execute() {
+            Ok(QueryResult::Rows(result)) => {
+                assert_eq!(result.columns, vec!["job"]);
+                assert_eq!(result.rows, vec![vec![Value::String("actor".into())]]);
+            },
+            _ => panic!("SELECT execute error"),
+        }
+    }
+
+    #[test]
+    fn test_exec_select_compound_where() {
+        let t = people_table_with(&[(1, "actor"), (2, "baker"), (3, "cook")]);
+
+        let cond = Condition::Or(
+            Box::new(Condition::Comparison {
+                column: "id".into(),
+                operator: Operator::Equal,
+                value: Value::Int(1),
+            }),
+            Box::new(Condition::Not(Box::new(Condition::Comparison {
+                column: "job".into(),
+                operator: Operator::LessThan,
+                value: Value::String("c".into()),
+            }))),
+        );
+
+        let mut cmd = SelectCommand { condition: Some(cond), ..select(&t, vec!["job".into()]) };
+
+        match cmd.execute() {
+            Ok(QueryResult::Rows(result)) => assert_eq!(render(&result), "job\nactor\ncook"),
+            _ => panic!("SELECT with compound WHERE error"),
+        }
+    }
+
+    #[test]
+    fn test_exec_select_order_by_limit_offset() {
+        let mut t = people_table_with(&[(1, "cook"), (2, "actor"), (3, "baker"), (4, "actor")]);
+        if let Some(record) = t.store.get_mut(&4) {
+            record.fields.insert("age".into(), Value::Int(40));
+        }
+
+        let mut cmd = SelectCommand {
+            order_by: vec![("job".into(), SortOrder::Ascending), ("age".into(), SortOrder::Descending)],
+            limit: Some(2),
+            offset: Some(1),
+            ..select(&t, vec!["id".into(), "job".into()])
+        };
+
+        match cmd.execute() {
+            Ok(QueryResult::Rows(result)) => assert_eq!(render(&result), "id, job\n2, actor\n3, baker"),
+            _ => panic!("SELECT with ORDER BY error"),
+        }
+    }
+
+    #[test]
+    fn test_exec_select_limit_stops_scan() {
+        let mut t = people_table_with(&[(1, "actor"), (2, "baker")]);
+        // A record without the projected field would fail the SELECT if it were visited.
+        t.store.insert(3, Record { fields: HashMap::new() });
+
         let mut cmd = SelectCommand {
-            table: &t,
-            fields: vec!["job".into()],
-            condition: Some(cond),
+            limit: Some(1),
+            offset: Some(1),
+            ..select(&t, vec!["job".into()])
         };
 
         match cmd.execute() {
-            Ok(Some(output)) => {
-                assert!(output.contains("actor"));
-                assert!(!output.contains("actress"));
-            },
-            _ => assert!(false, "SELECT execute error"),
+            Ok(QueryResult::Rows(result)) => assert_eq!(render(&result), "job\nbaker"),
+            Ok(other) => panic!("SELECT with LIMIT returned no rows: {:?}", other),
+            Err(e) => panic!("SELECT with LIMIT error: {:?}", e),
+        }
+    }
+
+    fn select_ids(t: &Table<i64>, condition: Condition) -> SelectCommand<'_, i64> {
+        SelectCommand { condition: Some(condition), ..select(t, vec!["id".into()]) }
+    }
+
+    fn job_condition(operator: Operator, job: &str) -> Condition {
+        Condition::Comparison { column: "job".into(), operator, value: Value::String(job.into()) }
+    }
+
+    #[test]
+    fn test_exec_select_uses_index() {
+        let mut t = people_table_with(&[(1, "actor"), (2, "baker"), (4, "cook")]);
+        if let Err(e) = t.create_index("people_job".into(), "job".into()) {
+            panic!("Not able to create an index: {:?}", e);
+        }
+        // A record without the filtered field would fail the SELECT if it were visited.
+        t.store.insert(3, Record { fields: HashMap::new() });
+
+        let equal = job_condition(Operator::Equal, "baker");
+        let range = Condition::And(
+            Box::new(job_condition(Operator::GreaterThan, "actor")),
+            Box::new(Condition::Comparison { column: "age".into(), operator: Operator::Equal, value: Value::Int(30) }),
+        );
+        for (condition, expected) in [(equal, "id\n2"), (range, "id\n2\n4")] {
+            match select_ids(&t, condition).execute() {
+                Ok(QueryResult::Rows(result)) => assert_eq!(render(&result), expected),
+                other => panic!("SELECT through index failed: {:?}", other),
+            }
+        }
+
+        // A condition the index cannot answer falls back to a scan and visits the bad record.
+        let not_equal = job_condition(Operator::NotEqual, "baker");
+        assert!(matches!(select_ids(&t, not_equal).execute(), Err(DbError::ColumnNotFound(_))));
+    }
+
+    fn id_condition(operator: Operator, id: i64) -> Condition {
+        Condition::Comparison { column: "id".into(), operator, value: Value::Int(id) }
+    }
+
+    #[test]
+    fn test_exec_select_primary_key_lookup_and_range() {
+        let mut t = people_table_with(&[(2, "actor"), (3, "baker"), (4, "cook"), (5, "driver")]);
+        // Records without any fields fail the SELECT if they are visited.
+        t.store.insert(1, Record { fields: HashMap::new() });
+        t.store.insert(6, Record { fields: HashMap::new() });
+
+        let and = |a, b| Condition::And(Box::new(a), Box::new(b));
+        let cases = [
+            (id_condition(Operator::Equal, 3), "id\n3"),
+            (and(id_condition(Operator::GreaterThan, 1), id_condition(Operator::LessThanOrEqual, 4)), "id\n2\n3\n4"),
+            (and(
+                and(id_condition(Operator::GreaterThanOrEqual, 4), job_condition(Operator::Equal, "driver")),
+                id_condition(Operator::LessThan, 6),
+            ), "id\n5"),
+            (and(id_condition(Operator::GreaterThan, 4), id_condition(Operator::LessThan, 3)), "id"),
+            (and(id_condition(Operator::GreaterThan, 3), id_condition(Operator::LessThan, 4)), "id"),
+        ];
+        for (condition, expected) in cases {
+            match select_ids(&t, condition).execute() {
+                Ok(QueryResult::Rows(result)) => assert_eq!(render(&result), expected),
+                other => panic!("SELECT on the primary key failed: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_exec_select_between_and_in_use_keys() {
+        let mut t = people_table_with(&[(2, "actor"), (3, "baker"), (4, "cook"), (5, "driver")]);
+        if let Err(e) = t.create_index("people_job".into(), "job".into()) {
+            panic!("Not able to create an index: {:?}", e);
+        }
+        // Records without any fields fail the SELECT if they are visited.
+        t.store.insert(1, Record { fields: HashMap::new() });
+        t.store.insert(6, Record { fields: HashMap::new() });
+
+        let literal = |value: Value| Expr::Literal(value);
+        let between = Condition::Between {
+            left: Box::new(Expr::Column("id".into())),
+            low: Box::new(literal(Value::Int(3))),
+            high: Box::new(literal(Value::Int(5))),
+        };
+        let jobs = Condition::In {
+            left: Box::new(Expr::Column("job".into())),
+            list: vec![literal(Value::String("cook".into())), literal(Value::String("actor".into()))],
+        };
+        for (condition, expected) in [(between, "id\n3\n4\n5"), (jobs, "id\n2\n4")] {
+            match select_ids(&t, condition).execute() {
+                Ok(QueryResult::Rows(result)) => assert_eq!(render(&result), expected),
+                other => panic!("SELECT through keys failed: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_like_in_and_between_truth() {
+        let record = Record {
+            fields: HashMap::from([
+                ("title".to_string(), Value::String("Homotopy Type Theory".into())),
+                ("year".to_string(), Value::Int(2013)),
+            ]),
+        };
+        let title = || Expr::Column("title".into());
+        let year = || Expr::Column("year".into());
+        let text = |s: &str| Expr::Literal(Value::String(s.into()));
+        let int = |n: i64| Expr::Literal(Value::Int(n));
+        let between = |low, high| {
+            Condition::Between { left: Box::new(year()), low: Box::new(low), high: Box::new(high) }
+        };
+        let like = |pattern: &str, case_insensitive| {
+            Condition::Like { left: Box::new(title()), pattern: Box::new(text(pattern)), case_insensitive }
+        };
+        let cases = [
+            (like("%Type%", false), Some(true)),
+            (like("%type%", false), Some(false)),
+            (like("%type%", true), Some(true)),
+            (like("Homotopy_Type%", false), Some(true)),
+            (like("H%y", true), Some(true)),
+            (like("H%y%T%x", false), Some(false)),
+            (Condition::In { left: Box::new(year()), list: vec![int(2012), int(2013)] }, Some(true)),
+            (Condition::In { left: Box::new(year()), list: vec![int(2012), Expr::Literal(Value::Null)] }, None),
+            (between(int(2013), int(2020)), Some(true)),
+            (between(int(2014), Expr::Literal(Value::Null)), Some(false)),
+            (between(int(2000), Expr::Literal(Value::Null)), None),
+        ];
+        for (condition, expected) in cases {
+            match condition.truth(&record) {
+                Ok(truth) => assert_eq!(truth, expected, "{:?}", condition),
+                Err(e) => panic!("Evaluating {:?} failed: {:?}", condition, e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_index_follows_updates_and_deletes() {
+        let mut t = people_table_with(&[(1, "actor"), (2, "baker")]);
+        if let Err(e) = t.create_index("people_job".into(), "job".into()) {
+            panic!("Not able to create an index: {:?}", e);
+        }
+
+        let mut cmd = UpdateCommand {
+            table: &mut t,
+            values: vec![("job".into(), Value::String("cook".into()))],
+            condition: Some(Condition::Comparison { column: "id".into(), operator: Operator::Equal, value: Value::Int(1) }),
+        };
+        if let Err(e) = cmd.execute() {
+            panic!("UPDATE failed: {:?}", e);
+        }
+        t.delete(&2);
+
+        let index = match t.index_on("job") {
+            Some(index) => index,
+            None => panic!("Index on job is missing"),
+        };
+        assert_eq!(index.lookup(&Operator::Equal, &Value::String("actor".into())), Some(BTreeSet::new()));
+        assert_eq!(index.lookup(&Operator::Equal, &Value::String("baker".into())), Some(BTreeSet::new()));
+        assert_eq!(index.lookup(&Operator::Equal, &Value::String("cook".into())), Some(BTreeSet::from([1])));
+    }
+
+    fn aggregate(function: AggregateFunction, column: Option<&str>) -> Aggregate {
+        Aggregate { function, column: column.map(|c| c.to_string()) }
+    }
+
+    #[test]
+    fn test_exec_select_group_by_having() {
+        let mut t = people_table_with(&[(1, "actor"), (2, "baker"), (3, "actor"), (4, "actor")]);
+        for (id, age) in [(1, 20), (3, 25), (4, 31)] {
+            if let Some(record) = t.store.get_mut(&id) {
+                record.fields.insert("age".into(), Value::Int(age));
+            }
+        }
+
+        let mut cmd = SelectCommand {
+            group_by: vec!["job".into()],
+            having: Some(Condition::AggregateComparison {
+                aggregate: aggregate(AggregateFunction::Count, None),
+                operator: Operator::GreaterThan,
+                value: Value::Int(1),
+            }),
+            ..select(&t, vec![
+                "job".into(),
+                SelectItem::Aggregate(aggregate(AggregateFunction::Count, None)).into(),
+                SelectItem::Aggregate(aggregate(AggregateFunction::Sum, Some("age"))).into(),
+                SelectItem::Aggregate(aggregate(AggregateFunction::Avg, Some("age"))).into(),
+                SelectItem::Aggregate(aggregate(AggregateFunction::Max, Some("height"))).into(),
+            ])
+        };
+
+        match cmd.execute() {
+            Ok(QueryResult::Rows(result)) => {
+                assert_eq!(render(&result), "job, COUNT(*), SUM(age), AVG(age), MAX(height)\nactor, 3, 76, 25.333333333333332, 175");
+                assert_eq!(result.types, vec![DataType::String, DataType::Int, DataType::Int, DataType::Float, DataType::Float]);
+                assert_eq!(result.get(0, "AVG(age)"), Some(&Value::Float(76.0 / 3.0)));
+            }
+            other => panic!("SELECT with GROUP BY error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_exec_select_groups_equal_values_together() {
+        let mut t = people_table_with(&[(1, "actor"), (2, "baker"), (3, "cook")]);
+        for (id, height) in [(1, 0.0), (2, -0.0), (3, 1.5)] {
+            if let Some(record) = t.store.get_mut(&id) {
+                record.fields.insert("height".into(), Value::Float(height));
+            }
+        }
+
+        let count = SelectItem::Aggregate(aggregate(AggregateFunction::Count, None));
+        let mut cmd = SelectCommand { group_by: vec!["height".into()], ..select(&t, vec!["height".into(), count.into()]) };
+
+        match cmd.execute() {
+            Ok(QueryResult::Rows(result)) => assert_eq!(result.rows, vec![
+                vec![Value::Float(0.0), Value::Int(2)],
+                vec![Value::Float(1.5), Value::Int(1)],
+            ]),
+            other => panic!("SELECT with GROUP BY error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_exec_select_avg_of_large_ints() {
+        let mut t = people_table_with(&[(1, "actor"), (2, "baker")]);
+        for id in [1, 2] {
+            if let Some(record) = t.store.get_mut(&id) {
+                record.fields.insert("age".into(), Value::Int(i64::MAX));
+            }
+        }
+
+        let mut cmd = select(&t, vec![SelectItem::Aggregate(aggregate(AggregateFunction::Avg, Some("age"))).into()]);
+
+        match cmd.execute() {
+            Ok(QueryResult::Rows(result)) => assert_eq!(result.rows, vec![vec![Value::Float(i64::MAX as f64)]]),
+            other => panic!("SELECT with AVG error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_exec_select_aggregates_without_rows() {
+        let t = people_table_with(&[]);
+
+        let mut cmd = select(&t, vec![
+            SelectItem::Aggregate(aggregate(AggregateFunction::Count, None)).into(),
+            SelectItem::Aggregate(aggregate(AggregateFunction::Avg, Some("age"))).into(),
+        ]);
+
+        match cmd.execute() {
+            Ok(QueryResult::Rows(result)) => assert_eq!(render(&result), "COUNT(*), AVG(age)\n0, NULL"),
+            other => panic!("SELECT with aggregates error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_exec_select_aggregate_type_errors() {
+        let t = people_table_with(&[(1, "actor")]);
+
+        let sum = SelectItem::Aggregate(aggregate(AggregateFunction::Sum, Some("job")));
+        let mut sum_of_strings = select(&t, vec![sum.into()]);
+        match sum_of_strings.execute() {
+            Err(DbError::TypeMismatch(_)) => {}
+            other => panic!("TypeMismatch expected, got: {:?}", other),
+        }
+
+        let count = SelectItem::Aggregate(aggregate(AggregateFunction::Count, None));
+        let mut ungrouped_column = select(&t, vec!["job".into(), count.clone().into()]);
+        match ungrouped_column.execute() {
+            Err(DbError::InvalidCommand(_)) => {}
+            other => panic!("InvalidCommand expected, got: {:?}", other),
+        }
+
+        // Rejected before any row is read, so an empty table fails the same way.
+        let empty = people_table_with(&[]);
+        let having = Condition::Comparison {
+            column: "age".into(),
+            operator: Operator::GreaterThan,
+            value: Value::Int(0),
+        };
+        let mut ungrouped_having = SelectCommand {
+            group_by: vec!["job".into()],
+            having: Some(having),
+            ..select(&empty, vec!["job".into(), count.into()])
+        };
+        match ungrouped_having.execute() {
+            Err(DbError::InvalidCommand(_)) => {}
+            other => panic!("InvalidCommand expected, got: {:?}", other),
+        }
+    }
+
+    fn jobs_table(rows: &[(i64, &str, i64)]) -> Table<i64> {
+        let schema = vec![
+            ("id".to_string(), DataType::Int),
+            ("title".to_string(), DataType::String),
+            ("pay".to_string(), DataType::Int),
+        ];
+        let mut t = Table::new("jobs".into(), schema, vec!["id".into()]);
+        for (id, title, pay) in rows {
+            let fields = HashMap::from([
+                ("id".to_string(), Value::Int(*id)),
+                ("title".to_string(), Value::String(title.to_string())),
+                ("pay".to_string(), Value::Int(*pay)),
+            ]);
+            if let Err(e) = t.insert(Record { fields }) {
+                panic!("Not able to insert a record: {:?}", e);
+            }
+        }
+        t
+    }
+
+    fn join_select<'a>(people: &'a Table<i64>, join: Join, jobs: &'a Table<i64>, fields: Vec<SelectField>) -> SelectCommand<'a, i64> {
+        SelectCommand { joins: vec![(join, jobs)], ..select(people, fields) }
+    }
+
+    #[test]
+    fn test_exec_select_inner_and_left_join() {
+        let people = people_table_with(&[(1, "actor"), (2, "baker"), (3, "cook")]);
+        let jobs = jobs_table(&[(10, "actor", 100), (20, "baker", 80)]);
+
+        for (kind, expected) in [(JoinKind::Inner, "people.id, pay\n1, 100\n2, 80"), (JoinKind::Left, "people.id, pay\n1, 100\n2, 80\n3, NULL")] {
+            let join = Join {
+                kind,
+                table: "jobs".into(),
+                left_column: "people.job".into(),
+                right_column: "jobs.title".into(),
+            };
+            let mut cmd = join_select(&people, join, &jobs, vec!["people.id".into(), "pay".into()]);
+            match cmd.execute() {
+                Ok(QueryResult::Rows(result)) => assert_eq!(render(&result), expected),
+                other => panic!("SELECT with {:?} JOIN error: {:?}", kind, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_exec_select_join_on_primary_key() {
+        let people = people_table_with(&[(1, "actor"), (2, "baker")]);
+        let jobs = jobs_table(&[(30, "thirty", 1), (31, "thirty one", 2)]);
+
+        let join = Join {
+            kind: JoinKind::Inner,
+            table: "jobs".into(),
+            left_column: "age".into(),
+            right_column: "id".into(),
+        };
+        let mut cmd = join_select(&people, join, &jobs, vec!["job".into(), "title".into()]);
+        cmd.condition = Some(Condition::Comparison {
+            column: "people.id".into(),
+            operator: Operator::Equal,
+            value: Value::Int(2),
+        });
+        match cmd.execute() {
+            Ok(QueryResult::Rows(result)) => assert_eq!(render(&result), "job, title\nbaker, thirty"),
+            other => panic!("SELECT with JOIN on primary key error: {:?}", other),
+        }
+
+        let ambiguous = Join {
+            kind: JoinKind::Inner,
+            table: "jobs".into(),
+            left_column: "age".into(),
+            right_column: "id".into(),
+        };
+        let mut cmd = join_select(&people, ambiguous, &jobs, vec!["id".into()]);
+        match cmd.execute() {
+            Err(DbError::ColumnNotFound(_)) => {}
+            other => panic!("Ambiguous column should not resolve, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_exec_select_wildcard_and_alias() {
+        let t = people_table_with(&[(1, "cook"), (2, "actor")]);
+
+        let mut cmd = SelectCommand {
+            order_by: vec![("occupation".into(), SortOrder::Ascending)],
+            ..select(&t, vec![
+                SelectField { item: "job".into(), alias: Some("occupation".into()) },
+                SelectItem::Wildcard.into(),
+            ])
+        };
+
+        match cmd.execute() {
+            Ok(QueryResult::Rows(result)) => assert_eq!(
+                render(&result),
+                "occupation, id, job, height, age, sex\nactor, 2, actor, 175, 30, male\ncook, 1, cook, 175, 30, male"
+            ),
+            other => panic!("SELECT * error: {:?}", other),
         }
     }
 
     #[test]
     fn test_exec_select_no_where() {
-        let mut t = Table::new("people".into(), get_people_schema(), "id".into());
+        let mut t = Table::new("people".into(), get_people_schema(), vec!["id".into()]);
         let mut f1 = HashMap::new();
         f1.insert("id".into(), Value::Int(1));
         t.store.insert(1, Record { fields: f1 });
 
-        let mut cmd = SelectCommand {
-            table: &t,
-            fields: vec!["id".into()],
-            condition: None,
-        };
+        let mut cmd = select(&t, vec!["id".into()]);
 
         match cmd.execute() {
-            Ok(Some(output)) => assert!(output.contains("1")),
-            _ => assert!(false, "SELECT without WHERE error"),
+            Ok(QueryResult::Rows(result)) => assert_eq!(result.rows, vec![vec![Value::Int(1)]]),
+            _ => panic!("SELECT without WHERE error"),
         }
     }
 
@@ -193,8 +1970,8 @@ mod tests {
         let mut cmd = CreateTableCommand {
             database: &mut db,
             name: "people".into(),
-            pk_name: "id".into(),
-            schema: get_people_schema(),
+            pk_columns: vec!["id".into()],
+            columns: get_people_schema().into_iter().map(ColumnDef::from).collect(),
         };
 
         let res = cmd.execute();
@@ -202,14 +1979,127 @@ mod tests {
         assert!(db.get_table("people").is_ok());
     }
 
+    fn nullable_table() -> Table<i64> {
+        let columns = vec![
+            ColumnDef::new("id", DataType::Int),
+            ColumnDef::new("job", DataType::String).not_null(),
+            ColumnDef::new("age", DataType::Int),
+        ];
+        let mut t = Table::new("people".into(), columns, vec!["id".into()]);
+        for (id, age) in [(1, Some(25)), (2, None), (3, Some(40))] {
+            let mut fields = HashMap::from([
+                ("id".to_string(), Value::Int(id)),
+                ("job".to_string(), Value::String("actor".into())),
+            ]);
+            if let Some(age) = age {
+                fields.insert("age".into(), Value::Int(age));
+            }
+            if let Err(e) = t.insert(Record { fields }) {
+                panic!("Not able to insert a record: {:?}", e);
+            }
+        }
+        t
+    }
+
+    #[test]
+    fn test_exec_insert_nullable_columns() {
+        let mut t = nullable_table();
+        assert_eq!(t.store[&2].fields.get("age"), Some(&Value::Null));
+
+        let without_job = Record { fields: HashMap::from([("id".to_string(), Value::Int(4))]) };
+        assert!(matches!(t.insert(without_job), Err(DbError::NullViolation(column)) if column == "job"));
+        let null_key = Record { fields: HashMap::from([
+            ("id".to_string(), Value::Null),
+            ("job".to_string(), Value::String("cook".into())),
+        ]) };
+        assert!(matches!(t.insert(null_key), Err(DbError::NullViolation(column)) if column == "id"));
+    }
+
+    #[test]
+    fn test_condition_three_valued_logic() {
+        let t = nullable_table();
+        let unknown = &t.store[&2];
+        let over_30 = || Condition::Comparison { column: "age".into(), operator: Operator::GreaterThan, value: Value::Int(30) };
+        let is_null = |negated| Condition::IsNull { column: "age".into(), negated };
+
+        let cases = [
+            (over_30(), false),
+            (Condition::Not(Box::new(over_30())), false),
+            (Condition::Or(Box::new(over_30()), Box::new(is_null(false))), true),
+            (Condition::Not(Box::new(Condition::And(Box::new(over_30()), Box::new(is_null(true))))), true),
+            (is_null(true), false),
+        ];
+        for (condition, expected) in cases {
+            match condition.evaluate(unknown) {
+                Ok(result) => assert_eq!(result, expected, "Wrong result for {:?}", condition),
+                Err(e) => panic!("Evaluating {:?} failed: {:?}", condition, e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_exec_select_orders_nulls_last() {
+        let t = nullable_table();
+        let cases = [
+            (SortOrder::Ascending, "id, age\n1, 25\n3, 40\n2, NULL"),
+            (SortOrder::Descending, "id, age\n2, NULL\n3, 40\n1, 25"),
+        ];
+        for (order, expected) in cases {
+            let mut cmd = SelectCommand {
+                order_by: vec![(SelectItem::from("age"), order)],
+                ..select(&t, vec!["id".into(), "age".into()])
+            };
+            match cmd.execute() {
+                Ok(QueryResult::Rows(result)) => assert_eq!(render(&result), expected),
+                other => panic!("SELECT with ORDER BY failed: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_exec_show_tables_and_describe() {
+        let mut db = setup_db();
+        let columns = vec![
+            ColumnDef::new("id", DataType::Int).auto_increment(),
+            ColumnDef::new("job", DataType::String).not_null().default(Value::String("none".into())),
+        ];
+        let mut people = Table::new("people".into(), columns, vec!["id".into()]);
+        if let Err(e) = people.create_index("people_job".into(), "job".into()) {
+            panic!("Not able to create an index: {:?}", e);
+        }
+        for table in [people, Table::new("jobs".into(), get_people_schema(), vec!["id".into()])] {
+            if let Err(e) = db.create_table(table) {
+                panic!("Not able to create a table: {:?}", e);
+            }
+        }
+
+        match (ShowTablesCommand { database: &db }).execute() {
+            Ok(QueryResult::Rows(result)) => assert_eq!(render(&result), "table, key, rows\njobs, id, 0\npeople, id, 0"),
+            other => panic!("SHOW TABLES failed: {:?}", other),
+        }
+
+        let table = match db.get_table("people") {
+            Ok(table) => table,
+            Err(e) => panic!("Table missing: {:?}", e),
+        };
+        match (DescribeCommand { table }).execute() {
+            Ok(QueryResult::Rows(result)) => assert_eq!(
+                render(&result),
+                "column, type, nullable, default, key, indexes\n\
+                 id, Int, false, NULL, PRIMARY AUTO_INCREMENT, NULL\n\
+                 job, String, false, none, NULL, people_job"
+            ),
+            other => panic!("DESCRIBE failed: {:?}", other),
+        }
+    }
+
     #[test]
     fn test_exec_insert() {
         let mut db = setup_db();
-        let t = Table::new("people".into(), get_people_schema(), "id".into());
+        let t = Table::new("people".into(), get_people_schema(), vec!["id".into()]);
 
         if let Err(e) = db.create_table(t) {
-            assert!(false, "Not able to create a table: {:?}", e);
-            return;
+            panic!("Not able to create a table: {:?}", e);
         }
 
         match db.get_table_mut("people") {
@@ -223,11 +2113,12 @@ mod tests {
 
                 let mut cmd = InsertCommand {
                     table: t_ref,
-                    record: Record { fields },
+                    records: vec![Record { fields }],
+                    on_conflict: OnConflict::Fail,
                 };
 
                 if let Err(e) = cmd.execute() {
-                    assert!(false, "INSERT execute error: {:?}", e);
+                    panic!("INSERT execute error: {:?}", e);
                 }
 
                 if let Some(rec) = t_ref.store.get(&1) {
@@ -237,18 +2128,18 @@ mod tests {
                         "The record fields do not match the inserted ones"
                     );
                 } else {
-                    assert!(false, "Record not found after INSERT");
+                    panic!("Record not found after INSERT");
                 }
             },
             Err(e) => {
-                assert!(false, "Not able to get 'people' table: {:?}", e);
+                panic!("Not able to get 'people' table: {:?}", e);
             }
         }
     }
 
     #[test]
     fn test_exec_delete() {
-        let mut t = Table::new("people".into(), get_people_schema(), "id".into());
+        let mut t = Table::new("people".into(), get_people_schema(), vec!["id".into()]);
         let mut f1 = HashMap::new();
         f1.insert("id".into(), Value::Int(5));
         t.store.insert(5, Record { fields: f1 });
@@ -259,7 +2150,113 @@ mod tests {
         };
 
         assert!(cmd.execute().is_ok());
-        assert!(t.store.get(&5).is_none());
+        assert!(!t.store.contains_key(&5));
+    }
+
+    fn people_table_with(rows: &[(i64, &str)]) -> Table<i64> {
+        let mut t = Table::new("people".into(), get_people_schema(), vec!["id".into()]);
+        for (id, job) in rows {
+            let mut fields = HashMap::new();
+            fields.insert("id".into(), Value::Int(*id));
+            fields.insert("job".into(), Value::String(job.to_string()));
+            fields.insert("height".into(), Value::Float(175.0));
+            fields.insert("age".into(), Value::Int(30));
+            fields.insert("sex".into(), Value::String("male".into()));
+            if let Err(e) = t.insert(Record { fields }) {
+                panic!("Not able to insert a record: {:?}", e);
+            }
+        }
+        t
+    }
+
+    #[test]
+    fn test_exec_update() {
+        let mut t = people_table_with(&[(1, "actor"), (2, "baker")]);
+
+        let mut cmd = UpdateCommand {
+            table: &mut t,
+            values: vec![("job".into(), Value::String("teacher".into()))],
+            condition: Some(Condition::Comparison {
+                column: "id".into(),
+                operator: Operator::Equal,
+                value: Value::Int(2),
+            }),
+        };
+
+        assert!(cmd.execute().is_ok());
+        assert_eq!(t.store[&1].fields.get("job"), Some(&Value::String("actor".into())));
+        assert_eq!(t.store[&2].fields.get("job"), Some(&Value::String("teacher".into())));
+    }
+
+    #[test]
+    fn test_exec_delete_where() {
+        let mut t = people_table_with(&[(1, "actor"), (2, "baker"), (3, "actor"), (4, "cook")]);
+        if let Err(e) = t.create_index("people_job".into(), "job".into()) {
+            panic!("Not able to create an index: {:?}", e);
+        }
+
+        let mut cmd = DeleteWhereCommand { table: &mut t, condition: Some(job_condition(Operator::Equal, "actor")) };
+        assert!(matches!(cmd.execute(), Ok(QueryResult::RowsAffected(2))));
+        assert_eq!(t.store.keys().copied().collect::<Vec<_>>(), vec![2, 4]);
+        assert!(matches!(t.index_on("job").and_then(|index| index.lookup(&Operator::Equal, &Value::String("actor".into()))),
+            Some(keys) if keys.is_empty()));
+
+        let mut cmd = DeleteWhereCommand { table: &mut t, condition: Some(id_condition(Operator::GreaterThan, 10)) };
+        assert!(matches!(cmd.execute(), Ok(QueryResult::RowsAffected(0))));
+        let mut cmd = DeleteWhereCommand { table: &mut t, condition: None };
+        assert!(matches!(cmd.execute(), Ok(QueryResult::RowsAffected(2))));
+        assert!(t.store.is_empty());
+    }
+
+    #[test]
+    fn test_exec_update_rekeys_primary_key() {
+        let mut t = people_table_with(&[(1, "actor"), (2, "baker")]);
+
+        let mut cmd = UpdateCommand {
+            table: &mut t,
+            values: vec![("id".into(), Value::Int(10))],
+            condition: Some(Condition::Comparison {
+                column: "job".into(),
+                operator: Operator::Equal,
+                value: Value::String("actor".into()),
+            }),
+        };
+
+        assert!(cmd.execute().is_ok());
+        assert!(!t.store.contains_key(&1));
+        assert_eq!(t.store[&10].fields.get("id"), Some(&Value::Int(10)));
+    }
+
+    #[test]
+    fn test_exec_update_rejects_invalid_changes() {
+        let mut t = people_table_with(&[(1, "actor"), (2, "baker")]);
+
+        let mut duplicate = UpdateCommand {
+            table: &mut t,
+            values: vec![("id".into(), Value::Int(2))],
+            condition: Some(Condition::Comparison {
+                column: "id".into(),
+                operator: Operator::Equal,
+                value: Value::Int(1),
+            }),
+        };
+        match duplicate.execute() {
+            Err(DbError::DuplicateKey) => {}
+            other => panic!("DuplicateKey error expected, got: {:?}", other),
+        }
+
+        let mut wrong_type = UpdateCommand {
+            table: &mut t,
+            values: vec![("age".into(), Value::String("old".into()))],
+            condition: None,
+        };
+        match wrong_type.execute() {
+            Err(DbError::TypeMismatch(_)) => {}
+            other => panic!("TypeMismatch error expected, got: {:?}", other),
+        }
+
+        assert_eq!(t.store.len(), 2);
+        assert_eq!(t.store[&1].fields.get("age"), Some(&Value::Int(30)));
     }
 
     #[test]
@@ -267,8 +2264,8 @@ mod tests {
 
         let mut table = Table::new(
             "users".into(),
-            HashMap::from([("id".to_string(), DataType::Int)]),
-            "id".into()
+            vec![("id".to_string(), DataType::Int)],
+            vec!["id".into()]
         );
 
         let rec1 = Record {
@@ -279,15 +2276,16 @@ mod tests {
 
         let mut cmd = InsertCommand {
             table: &mut table,
-            record: rec1,
+            records: vec![rec1],
+            on_conflict: OnConflict::Fail,
         };
 
         let result = cmd.execute();
 
         match result {
-            Err(DbError::DuplicateKey) => assert!(true),
-            Ok(_) => assert!(false, "There should be duplicate key error"),
-            Err(e) => assert!(false, "DuplicateKey error expected, got: {:?}", e),
+            Err(DbError::DuplicateKey) => {}
+            Ok(_) => panic!("There should be duplicate key error"),
+            Err(e) => panic!("DuplicateKey error expected, got: {:?}", e),
         }
     }
 }
\ No newline at end of file
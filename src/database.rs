@@ -1,22 +1,223 @@
-use std::collections::{BTreeMap, HashMap};
-use crate::commands::{Command, CreateTableCommand, InsertCommand, SelectCommand, DeleteCommand};
-use crate::domain::{DataType, DatabaseKey, Record};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::Arc;
+use crate::commands::{
+    Command, CreateTableCommand, InsertCommand, SelectCommand, UpdateCommand, DeleteCommand, DeleteWhereCommand,
+    CreateIndexCommand, DropIndexCommand, DropTableCommand, TruncateCommand, AlterTableCommand, ShowTablesCommand,
+    DescribeCommand, OnConflict, Operator,
+};
+use crate::domain::{ColumnDef, DataType, DatabaseKey, IndexKey, Key, Record, Value};
 use crate::error::{DbError, DbResult};
+use crate::functions::{builtin, UserFunction, UserFunctions};
 use crate::queries::Query;
+use crate::results::QueryResult;
+use crate::storage;
+use crate::wal::{SyncPolicy, Wal, WalRecord};
 
 pub struct Database<K: DatabaseKey> {
     tables: HashMap<String, Table<K>>,
+    wal: Option<Wal>,
+    pending: Vec<WalRecord>,
+    transaction: Option<Transaction<K>>,
+    functions: UserFunctions,
+}
+
+/// State of an open transaction. Every table is copied the first time it is modified, so
+/// ROLLBACK only has to put the copies back; `None` marks a table created in the transaction.
+struct Transaction<K: DatabaseKey> {
+    snapshots: HashMap<String, Option<Table<K>>>,
+    changes: Vec<WalRecord>,
 }
 
 impl<K: DatabaseKey> Database<K> {
     pub fn new() -> Database<K> {
         Self {
             tables: HashMap::new(),
+            wal: None,
+            pending: Vec::new(),
+            transaction: None,
+            functions: HashMap::new(),
+        }
+    }
+
+    /// Loads every table previously flushed to `dir` and replays the write-ahead log kept
+    /// there. A directory that does not exist yet opens as an empty database.
+    pub fn open<P: AsRef<Path>>(dir: P) -> DbResult<Database<K>> {
+        Self::open_with(dir, SyncPolicy::Always)
+    }
+
+    /// Like [`Database::open`], with a chosen policy for syncing the log to disk.
+    pub fn open_with<P: AsRef<Path>>(dir: P, policy: SyncPolicy) -> DbResult<Database<K>> {
+        let mut database = Database::new();
+        let (tables, lsn) = storage::load_tables(dir.as_ref())?;
+        for table in tables {
+            database.create_table(table)?;
+        }
+
+        let (wal, records) = Wal::recover(dir.as_ref(), lsn, policy)?;
+        for record in records {
+            database.apply(record)?;
+        }
+        for table in database.tables.values_mut() {
+            table.log = Some(Vec::new());
+        }
+        database.wal = Some(wal);
+        Ok(database)
+    }
+
+    /// Writes all tables to `dir`, replacing whatever was stored there before. Not allowed
+    /// inside a transaction, whose changes are not in the log yet.
+    pub fn flush<P: AsRef<Path>>(&self, dir: P) -> DbResult<()> {
+        if self.in_transaction() {
+            return Err(DbError::InvalidCommand("Cannot flush inside a transaction".into()));
+        }
+        let lsn = self.wal.as_ref().map_or(0, |wal| wal.last_lsn());
+        storage::save_tables(dir.as_ref(), self.tables.values(), lsn)
+    }
+
+    /// Writes a snapshot into the directory the database was opened from and empties the
+    /// log. Does nothing for a database that was not opened from a directory.
+    pub fn checkpoint(&mut self) -> DbResult<()> {
+        if self.in_transaction() {
+            return Err(DbError::InvalidCommand("Cannot checkpoint inside a transaction".into()));
+        }
+        if let Some(wal) = &mut self.wal {
+            storage::save_tables(wal.dir(), self.tables.values(), wal.last_lsn())?;
+            wal.reset()?;
+        }
+        Ok(())
+    }
+
+    /// Runs a query and, once it has succeeded, appends its changes to the log. Inside a
+    /// transaction the changes are held back until COMMIT.
+    pub fn execute(&mut self, query: Query) -> DbResult<QueryResult> {
+        let result = match query {
+            Query::Begin => return self.begin(),
+            Query::Commit => return self.commit(),
+            Query::Rollback => return self.rollback(),
+            mut query => {
+                query.bind(&self.functions);
+                run_generic_query(self, query)
+            }
+        };
+
+        let mut changes = std::mem::take(&mut self.pending);
+        for table in self.tables.values_mut() {
+            if let Some(log) = &mut table.log {
+                changes.append(log);
+            }
+        }
+        if result.is_ok() {
+            if let Some(transaction) = &mut self.transaction {
+                transaction.changes.append(&mut changes);
+            } else if let Some(wal) = &mut self.wal {
+                wal.append(&changes)?;
+            }
+        }
+        result
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    pub fn begin(&mut self) -> DbResult<QueryResult> {
+        if self.in_transaction() {
+            return Err(DbError::InvalidCommand("Transaction already in progress".into()));
+        }
+        self.transaction = Some(Transaction { snapshots: HashMap::new(), changes: Vec::new() });
+        Ok(QueryResult::Message("Transaction started.".into()))
+    }
+
+    /// Keeps the changes of the open transaction and writes them to the log as one frame,
+    /// so recovery replays either all of them or none.
+    pub fn commit(&mut self) -> DbResult<QueryResult> {
+        let transaction = self.transaction.take()
+            .ok_or_else(|| DbError::InvalidCommand("No transaction in progress".into()))?;
+        if let Some(wal) = &mut self.wal {
+            wal.append(&transaction.changes)?;
+        }
+        Ok(QueryResult::Message("Transaction committed.".into()))
+    }
+
+    /// Restores every table modified, created or removed by the open transaction.
+    pub fn rollback(&mut self) -> DbResult<QueryResult> {
+        let transaction = self.transaction.take()
+            .ok_or_else(|| DbError::InvalidCommand("No transaction in progress".into()))?;
+        for (name, snapshot) in transaction.snapshots {
+            match snapshot {
+                Some(table) => self.tables.insert(name, table),
+                None => self.tables.remove(&name),
+            };
+        }
+        Ok(QueryResult::Message("Transaction rolled back.".into()))
+    }
+
+    /// Redoes a change read back from the log.
+    fn apply(&mut self, record: WalRecord) -> DbResult<()> {
+        match record {
+            WalRecord::CreateTable { name, pk_columns, columns } => {
+                self.create_table(Table::new(name, columns, pk_columns))
+            }
+            WalRecord::Insert { table, record } => self.get_table_mut(&table)?.insert(record),
+            WalRecord::Delete { table, key } => {
+                let key = K::from_values(&key).ok_or(DbError::KeyMismatch)?;
+                self.get_table_mut(&table)?.delete(&key);
+                Ok(())
+            }
+            WalRecord::CreateIndex { table, name, column } => self.get_table_mut(&table)?.create_index(name, column),
+            WalRecord::DropIndex { table, name } => {
+                self.get_table_mut(&table)?.drop_index(&name);
+                Ok(())
+            }
+            WalRecord::DropTable { name } => self.drop_table(&name),
+            WalRecord::Truncate { table } => {
+                self.get_table_mut(&table)?.truncate();
+                Ok(())
+            }
+            WalRecord::AddColumn { table, column } => self.get_table_mut(&table)?.add_column(column),
+            WalRecord::DropColumn { table, column } => self.get_table_mut(&table)?.drop_column(&column),
+            WalRecord::RenameColumn { table, from, to } => self.get_table_mut(&table)?.rename_column(&from, &to),
+            WalRecord::RenameTable { from, to } => self.rename_table(&from, &to),
         }
     }
 
-    pub fn create_table(&mut self, table: Table<K>) -> DbResult<()> {
+    /// Creates an index on `table.column`. Index names are unique across the database.
+    pub fn create_index(&mut self, name: String, table: &str, column: String) -> DbResult<()> {
+        if self.index_table(&name).is_some() {
+            return Err(DbError::InvalidCommand(format!("Index {} already exists", name)));
+        }
+        self.get_table_mut(table)?.create_index(name, column)
+    }
+
+    pub fn drop_index(&mut self, name: &str) -> DbResult<()> {
+        let table = self.index_table(name)
+            .ok_or_else(|| DbError::InvalidCommand(format!("Index {} not found", name)))?;
+        self.get_table_mut(&table)?.drop_index(name);
+        Ok(())
+    }
+
+    /// Name of the table owning the index called `name`.
+    fn index_table(&self, name: &str) -> Option<String> {
+        self.tables.values()
+            .find(|table| table.indexes.contains_key(name))
+            .map(|table| table.name.clone())
+    }
+
+    pub fn create_table(&mut self, mut table: Table<K>) -> DbResult<()> {
         if !self.tables.contains_key(&table.name) {
+            if self.wal.is_some() {
+                self.pending.push(WalRecord::CreateTable {
+                    name: table.name.clone(),
+                    pk_columns: table.pk_columns.clone(),
+                    columns: table.column_defs(),
+                });
+                table.log = Some(Vec::new());
+            }
+            if let Some(transaction) = &mut self.transaction {
+                transaction.snapshots.entry(table.name.clone()).or_insert(None);
+            }
             self.tables.insert(table.name.to_string(), table);
             Ok(())
         } else {
@@ -24,96 +225,1264 @@ impl<K: DatabaseKey> Database<K> {
         }
     }
 
-    pub fn get_table(&mut self, table: &str) -> DbResult<&Table<K>> {
+    /// Makes a Rust function callable by name from queries. Names are case-insensitive and
+    /// cannot be taken from built-in or aggregate functions. Functions are not stored with
+    /// the data, so they have to be registered again after the database is reopened.
+    pub fn register_function(&mut self, function: UserFunction) -> DbResult<()> {
+        let name = function.name.to_uppercase();
+        let mut chars = name.chars();
+        let is_ident = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_ident {
+            return Err(DbError::InvalidCommand(format!("'{}' is not a valid function name", function.name)));
+        }
+        if builtin(&name).is_some() || ["COUNT", "SUM", "AVG", "MIN", "MAX"].contains(&name.as_str()) {
+            return Err(DbError::InvalidCommand(format!("{} is a built-in function", function.name)));
+        }
+        if self.functions.contains_key(&name) {
+            return Err(DbError::InvalidCommand(format!("Function {} is already registered", function.name)));
+        }
+        self.functions.insert(name, Arc::new(function));
+        Ok(())
+    }
+
+    /// Names of all tables in alphabetical order.
+    pub fn table_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.tables.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    /// Describes the schema, primary key, size and indexes of a table.
+    pub fn describe(&self, table: &str) -> DbResult<TableInfo> {
+        self.get_table(table).map(Table::info)
+    }
+
+    pub fn drop_table(&mut self, name: &str) -> DbResult<()> {
+        let table = self.tables.remove(name).ok_or_else(|| DbError::TableNotFound(name.to_string()))?;
+        if self.wal.is_some() {
+            self.pending.push(WalRecord::DropTable { name: name.to_string() });
+        }
+        if let Some(transaction) = &mut self.transaction {
+            transaction.snapshots.entry(name.to_string()).or_insert(Some(table));
+        }
+        Ok(())
+    }
+
+    pub fn rename_table(&mut self, from: &str, to: &str) -> DbResult<()> {
+        if self.tables.contains_key(to) {
+            return Err(DbError::TableAlreadyExists(to.to_string()));
+        }
+        let mut table = self.tables.remove(from).ok_or_else(|| DbError::TableNotFound(from.to_string()))?;
+        if let Some(transaction) = &mut self.transaction {
+            transaction.snapshots.entry(from.to_string()).or_insert_with(|| Some(table.clone()));
+            transaction.snapshots.entry(to.to_string()).or_insert(None);
+        }
+        if self.wal.is_some() {
+            self.pending.push(WalRecord::RenameTable { from: from.to_string(), to: to.to_string() });
+        }
+        table.name = to.to_string();
+        self.tables.insert(to.to_string(), table);
+        Ok(())
+    }
+
+    pub fn get_table(&self, table: &str) -> DbResult<&Table<K>> {
         self.tables.get(table).ok_or_else(|| DbError::TableNotFound(table.to_string()))
     }
 
     pub fn get_table_mut(&mut self, table: &str) -> DbResult<&mut Table<K>> {
-        self.tables.get_mut(table).ok_or_else(|| DbError::TableNotFound(table.to_string()))
+        let found = self.tables.get_mut(table).ok_or_else(|| DbError::TableNotFound(table.to_string()))?;
+        if let Some(transaction) = &mut self.transaction {
+            transaction.snapshots.entry(table.to_string()).or_insert_with(|| Some(found.clone()));
+        }
+        Ok(found)
+    }
+}
+
+impl<K: DatabaseKey> Default for Database<K> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
+/// Read-only description of a table, as returned by [`Database::describe`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableInfo {
+    pub name: String,
+    pub pk_columns: Vec<String>,
+    pub columns: Vec<ColumnDef>,
+    pub row_count: usize,
+    pub indexes: Vec<IndexInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexInfo {
+    pub name: String,
+    pub column: String,
+}
+
+#[derive(Clone)]
 pub struct Table<K: DatabaseKey> {
     pub name: String,
-    pk_name: String,
+    /// Primary-key columns in key order; records are ordered by their values lexicographically.
+    pk_columns: Vec<String>,
+    columns: Vec<String>,
     schema: HashMap<String, DataType>,
-    pub store: BTreeMap<K, Record>
+    /// Columns that may not hold NULL, always including the primary key.
+    not_null: HashSet<String>,
+    defaults: HashMap<String, Value>,
+    auto_increment: bool,
+    pub store: BTreeMap<K, Record>,
+    indexes: BTreeMap<String, Index<K>>,
+    /// Changes not yet written to the log; `None` while no log is attached.
+    log: Option<Vec<WalRecord>>,
+}
+
+/// What [`Table::upsert_all`] did with a record.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InsertOutcome {
+    Inserted,
+    /// The record replaced or updated the one stored under its key.
+    Updated,
+    /// The key was taken and the record was dropped.
+    Skipped,
+}
+
+/// A secondary index mapping every non-NULL value of a column to the keys of the records
+/// holding it.
+#[derive(Clone)]
+pub struct Index<K: DatabaseKey> {
+    column: String,
+    entries: BTreeMap<IndexKey, BTreeSet<K>>,
+}
+
+impl<K: DatabaseKey> Index<K> {
+    pub fn column(&self) -> &str {
+        &self.column
+    }
+
+    /// Keys of the records whose column satisfies `column <operator> value`, in key order,
+    /// or `None` for an operator the index cannot answer.
+    pub fn lookup(&self, operator: &Operator, value: &Value) -> Option<BTreeSet<K>> {
+        if *value == Value::Null {
+            // A comparison with NULL never holds.
+            return Some(BTreeSet::new());
+        }
+        let key = IndexKey(value.clone());
+        let range = match operator {
+            Operator::Equal => (Bound::Included(key.clone()), Bound::Included(key)),
+            Operator::GreaterThan => (Bound::Excluded(key), Bound::Unbounded),
+            Operator::GreaterThanOrEqual => (Bound::Included(key), Bound::Unbounded),
+            Operator::LessThan => (Bound::Unbounded, Bound::Excluded(key)),
+            Operator::LessThanOrEqual => (Bound::Unbounded, Bound::Included(key)),
+            Operator::NotEqual => return None,
+        };
+        Some(self.entries.range(range).flat_map(|(_, keys)| keys.iter().cloned()).collect())
+    }
+
+    fn add(&mut self, key: &K, record: &Record) {
+        if let Some(value) = record.fields.get(&self.column)
+            && *value != Value::Null {
+            self.entries.entry(IndexKey(value.clone())).or_default().insert(key.clone());
+        }
+    }
+
+    fn remove(&mut self, key: &K, record: &Record) {
+        let Some(value) = record.fields.get(&self.column) else {
+            return;
+        };
+        let value = IndexKey(value.clone());
+        if let Some(keys) = self.entries.get_mut(&value) {
+            keys.remove(key);
+            if keys.is_empty() {
+                self.entries.remove(&value);
+            }
+        }
+    }
 }
 
 impl<K: DatabaseKey> Table<K> {
-    pub fn new(name: String, schema: HashMap<String, DataType>, pk_name: String) -> Table<K> {
+    pub fn new<C: Into<ColumnDef>>(name: String, columns: Vec<C>, pk_columns: Vec<String>) -> Table<K> {
+        let columns: Vec<ColumnDef> = columns.into_iter().map(Into::into).collect();
+        let mut not_null: HashSet<String> = columns.iter()
+            .filter(|column| !column.nullable)
+            .map(|column| column.name.clone())
+            .collect();
+        not_null.extend(pk_columns.iter().cloned());
+        let defaults = columns.iter()
+            .filter_map(|column| Some((column.name.clone(), column.default.clone()?)))
+            .collect();
+        let auto_increment = columns.iter()
+            .any(|column| column.auto_increment && pk_columns == [column.name.as_str()]);
         Self {
             name,
-            schema,
-            pk_name,
-            store: BTreeMap::new()
+            columns: columns.iter().map(|column| column.name.clone()).collect(),
+            schema: columns.into_iter().map(|column| (column.name, column.data_type)).collect(),
+            not_null,
+            defaults,
+            auto_increment,
+            pk_columns,
+            store: BTreeMap::new(),
+            indexes: BTreeMap::new(),
+            log: None,
         }
     }
 
+    /// Inserts a record. Columns it does not mention get their default value, or NULL
+    /// without one, and a missing auto-increment key is generated.
     pub fn insert(&mut self, record: Record) -> DbResult<()> {
-        record.validate(&self.schema)?;
+        self.insert_all(vec![record]).map(|_| ())
+    }
+
+    /// Inserts several records as if one by one, but every record is completed and validated
+    /// before any is stored, so a failing record leaves the table untouched.
+    pub fn insert_all(&mut self, records: Vec<Record>) -> DbResult<usize> {
+        self.upsert_all(records, &OnConflict::Fail).map(|outcomes| outcomes.len())
+    }
+
+    /// Like [`Table::insert_all`], but a record whose key is already taken, by a stored record
+    /// or an earlier one in `records`, is resolved as `on_conflict` says. Returns the key of
+    /// every record in order together with what happened to it.
+    pub fn upsert_all(&mut self, records: Vec<Record>, on_conflict: &OnConflict) -> DbResult<Vec<(K, InsertOutcome)>> {
+        let mut next_id = self.next_id();
+        let mut written: BTreeMap<K, Record> = BTreeMap::new();
+        let mut outcomes = Vec::with_capacity(records.len());
+        for mut record in records {
+            if self.auto_increment {
+                let pk = &self.pk_columns[0];
+                if matches!(record.fields.get(pk), None | Some(Value::Null)) {
+                    record.fields.insert(pk.clone(), Value::Int(next_id));
+                }
+                if let Some(Value::Int(id)) = record.fields.get(pk) {
+                    next_id = next_id.max(id.saturating_add(1));
+                }
+            }
+            for column in &self.columns {
+                let default = self.defaults.get(column).cloned().unwrap_or(Value::Null);
+                record.fields.entry(column.clone()).or_insert(default);
+            }
+            self.check(&record)?;
+
+            let key = self.key_of(&record)?;
+            let existing = written.get(&key).or_else(|| self.store.get(&key)).cloned();
+            let outcome = match (existing, on_conflict) {
+                (None, _) => {
+                    written.insert(key.clone(), record);
+                    InsertOutcome::Inserted
+                }
+                (Some(_), OnConflict::Replace) => {
+                    written.insert(key.clone(), record);
+                    InsertOutcome::Updated
+                }
+                (Some(_), OnConflict::Fail) => return Err(DbError::DuplicateKey),
+                (Some(_), OnConflict::DoNothing) => InsertOutcome::Skipped,
+                (Some(mut existing), OnConflict::DoUpdate(values)) => {
+                    for (column, value) in values {
+                        existing.fields.insert(column.clone(), value.clone());
+                    }
+                    self.check(&existing)?;
+                    if self.key_of(&existing)? != key {
+                        return Err(DbError::InvalidCommand(
+                            "ON CONFLICT DO UPDATE cannot change the primary key".into()
+                        ));
+                    }
+                    written.insert(key.clone(), existing);
+                    InsertOutcome::Updated
+                }
+            };
+            outcomes.push((key, outcome));
+        }
+
+        for (key, record) in written {
+            self.delete(&key);
+            self.put(key, record);
+        }
+        Ok(outcomes)
+    }
+
+    /// Replaces the records stored under the given keys. Every new record is validated and
+    /// re-keyed by its primary key before anything is modified, so a failing change leaves
+    /// the table untouched.
+    pub fn update(&mut self, changes: Vec<(K, Record)>) -> DbResult<usize> {
+        let mut rekeyed = Vec::with_capacity(changes.len());
+        for (old_key, record) in changes {
+            self.check(&record)?;
+            let new_key = self.key_of(&record)?;
+            rekeyed.push((old_key, new_key, record));
+        }
+
+        let old_keys: BTreeSet<&K> = rekeyed.iter().map(|(old, _, _)| old).collect();
+        let mut new_keys = BTreeSet::new();
+        for (_, new_key, _) in &rekeyed {
+            if !new_keys.insert(new_key) || (self.store.contains_key(new_key) && !old_keys.contains(new_key)) {
+                return Err(DbError::DuplicateKey);
+            }
+        }
+
+        for (old_key, _, _) in &rekeyed {
+            self.delete(old_key);
+        }
+        let count = rekeyed.len();
+        for (_, new_key, record) in rekeyed {
+            self.put(new_key, record);
+        }
+        Ok(count)
+    }
 
-        let pk_value = record.fields.get(self.pk_name.as_str()).ok_or_else(|| {
-            DbError::ColumnNotFound(format!("Primary key {} not found", self.pk_name.clone()))
-        })?;
+    pub fn delete(&mut self, key: &K) -> Option<Record> {
+        let record = self.store.remove(key)?;
+        for index in self.indexes.values_mut() {
+            index.remove(key, &record);
+        }
+        if self.log.is_some()
+            && let Ok(key) = self.key_values(&record)
+            && let Some(log) = &mut self.log {
+            log.push(WalRecord::Delete { table: self.name.clone(), key });
+        }
+        Some(record)
+    }
 
-        let key = K::from_value(pk_value).ok_or(DbError::KeyMismatch)?;
+    /// One more than the largest Int key stored, so ids freed by deleting the last record
+    /// are handed out again.
+    fn next_id(&self) -> i64 {
+        match self.store.values().next_back().and_then(|record| record.fields.get(&self.pk_columns[0])) {
+            Some(Value::Int(id)) => id.saturating_add(1),
+            _ => 1,
+        }
+    }
 
-        if self.store.contains_key(&key) {
-            return Err(DbError::DuplicateKey);
+    /// Validates the types of a record and that no NOT NULL column holds NULL.
+    fn check(&self, record: &Record) -> DbResult<()> {
+        record.validate(&self.schema)?;
+        for column in &self.columns {
+            if self.not_null.contains(column) && record.fields.get(column) == Some(&Value::Null) {
+                return Err(DbError::NullViolation(column.clone()));
+            }
         }
+        Ok(())
+    }
 
+    /// Stores an already validated record under a key that is known to be free.
+    fn put(&mut self, key: K, record: Record) {
+        for index in self.indexes.values_mut() {
+            index.add(&key, &record);
+        }
+        if let Some(log) = &mut self.log {
+            log.push(WalRecord::Insert { table: self.name.clone(), record: record.clone() });
+        }
         self.store.insert(key, record);
+    }
+
+    /// Builds an index over the records already stored in the table.
+    pub fn create_index(&mut self, name: String, column: String) -> DbResult<()> {
+        if !self.has_column(&column) {
+            return Err(DbError::ColumnNotFound(column));
+        }
+        if self.indexes.contains_key(&name) {
+            return Err(DbError::InvalidCommand(format!("Index {} already exists", name)));
+        }
+        let mut index = Index { column: column.clone(), entries: BTreeMap::new() };
+        for (key, record) in &self.store {
+            index.add(key, record);
+        }
+        if let Some(log) = &mut self.log {
+            log.push(WalRecord::CreateIndex { table: self.name.clone(), name: name.clone(), column });
+        }
+        self.indexes.insert(name, index);
         Ok(())
     }
 
-    pub fn delete(&mut self, key: &K) -> Option<Record> {
-        self.store.remove(key)
+    pub fn drop_index(&mut self, name: &str) -> Option<Index<K>> {
+        let index = self.indexes.remove(name)?;
+        if let Some(log) = &mut self.log {
+            log.push(WalRecord::DropIndex { table: self.name.clone(), name: name.to_string() });
+        }
+        Some(index)
+    }
+
+    /// Removes every record and returns how many there were.
+    pub fn truncate(&mut self) -> usize {
+        let count = self.store.len();
+        self.store.clear();
+        for index in self.indexes.values_mut() {
+            index.entries.clear();
+        }
+        if let Some(log) = &mut self.log {
+            log.push(WalRecord::Truncate { table: self.name.clone() });
+        }
+        count
+    }
+
+    /// Appends a column, setting it to its default, or NULL without one, in every record.
+    pub fn add_column(&mut self, column: ColumnDef) -> DbResult<()> {
+        if self.has_column(&column.name) {
+            return Err(DbError::InvalidCommand(format!("Duplicate column '{}'", column.name)));
+        }
+        column.check(&self.pk_columns)?;
+        let value = column.default.clone().unwrap_or(Value::Null);
+        if value == Value::Null && !column.nullable && !self.store.is_empty() {
+            return Err(DbError::NullViolation(column.name));
+        }
+
+        if let Some(log) = &mut self.log {
+            log.push(WalRecord::AddColumn { table: self.name.clone(), column: column.clone() });
+        }
+        for record in self.store.values_mut() {
+            record.fields.insert(column.name.clone(), value.clone());
+        }
+        if !column.nullable {
+            self.not_null.insert(column.name.clone());
+        }
+        if let Some(default) = column.default {
+            self.defaults.insert(column.name.clone(), default);
+        }
+        self.schema.insert(column.name.clone(), column.data_type);
+        self.columns.push(column.name);
+        Ok(())
+    }
+
+    /// Removes a column from the schema and every record, along with its indexes.
+    pub fn drop_column(&mut self, column: &str) -> DbResult<()> {
+        if !self.has_column(column) {
+            return Err(DbError::ColumnNotFound(column.to_string()));
+        }
+        if self.pk_columns.iter().any(|pk| pk == column) {
+            return Err(DbError::InvalidCommand("Cannot drop a primary key column".into()));
+        }
+
+        if let Some(log) = &mut self.log {
+            log.push(WalRecord::DropColumn { table: self.name.clone(), column: column.to_string() });
+        }
+        for record in self.store.values_mut() {
+            record.fields.remove(column);
+        }
+        self.indexes.retain(|_, index| index.column != column);
+        self.columns.retain(|name| name != column);
+        self.schema.remove(column);
+        self.not_null.remove(column);
+        self.defaults.remove(column);
+        Ok(())
+    }
+
+    pub fn rename_column(&mut self, from: &str, to: &str) -> DbResult<()> {
+        if !self.has_column(from) {
+            return Err(DbError::ColumnNotFound(from.to_string()));
+        }
+        if self.has_column(to) {
+            return Err(DbError::InvalidCommand(format!("Duplicate column '{}'", to)));
+        }
+
+        if let Some(log) = &mut self.log {
+            log.push(WalRecord::RenameColumn { table: self.name.clone(), from: from.to_string(), to: to.to_string() });
+        }
+        for record in self.store.values_mut() {
+            if let Some(value) = record.fields.remove(from) {
+                record.fields.insert(to.to_string(), value);
+            }
+        }
+        for index in self.indexes.values_mut().filter(|index| index.column == from) {
+            index.column = to.to_string();
+        }
+        for name in self.columns.iter_mut().filter(|name| *name == from) {
+            *name = to.to_string();
+        }
+        if let Some(data_type) = self.schema.remove(from) {
+            self.schema.insert(to.to_string(), data_type);
+        }
+        if self.not_null.remove(from) {
+            self.not_null.insert(to.to_string());
+        }
+        if let Some(default) = self.defaults.remove(from) {
+            self.defaults.insert(to.to_string(), default);
+        }
+        for pk in self.pk_columns.iter_mut().filter(|pk| *pk == from) {
+            *pk = to.to_string();
+        }
+        Ok(())
+    }
+
+    pub fn info(&self) -> TableInfo {
+        TableInfo {
+            name: self.name.clone(),
+            pk_columns: self.pk_columns.clone(),
+            columns: self.column_defs(),
+            row_count: self.store.len(),
+            indexes: self.indexes.iter()
+                .map(|(name, index)| IndexInfo { name: name.clone(), column: index.column.clone() })
+                .collect(),
+        }
+    }
+
+    /// Indexes of the table by name.
+    pub fn indexes(&self) -> &BTreeMap<String, Index<K>> {
+        &self.indexes
+    }
+
+    /// Any index built on `column`.
+    pub fn index_on(&self, column: &str) -> Option<&Index<K>> {
+        self.indexes.values().find(|index| index.column == column)
+    }
+
+    /// Primary-key columns in key order.
+    pub fn pk_columns(&self) -> &[String] {
+        &self.pk_columns
+    }
+
+    /// Whether the primary key is made of `column` alone.
+    pub fn is_pk(&self, column: &str) -> bool {
+        self.pk_columns == [column]
+    }
+
+    /// Column names in the order they were declared in CREATE.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn schema(&self) -> &HashMap<String, DataType> {
+        &self.schema
+    }
+
+    /// Full column declarations in declaration order.
+    pub fn column_defs(&self) -> Vec<ColumnDef> {
+        self.columns.iter()
+            .map(|column| ColumnDef {
+                name: column.clone(),
+                data_type: self.schema[column].clone(),
+                nullable: !self.not_null.contains(column),
+                default: self.defaults.get(column).cloned(),
+                auto_increment: self.auto_increment && self.is_pk(column),
+            })
+            .collect()
+    }
+
+    pub fn is_nullable(&self, column: &str) -> bool {
+        !self.not_null.contains(column)
+    }
+
+    pub fn has_column(&self, column: &str) -> bool {
+        self.schema.contains_key(column)
+    }
+
+    pub fn column_type(&self, column: &str) -> Option<&DataType> {
+        self.schema.get(column)
+    }
+
+    fn key_of(&self, record: &Record) -> DbResult<K> {
+        K::from_values(&self.key_values(record)?).ok_or(DbError::KeyMismatch)
+    }
+
+    /// Values of the primary-key columns of a record, in key order.
+    fn key_values(&self, record: &Record) -> DbResult<Vec<Value>> {
+        self.pk_columns.iter()
+            .map(|pk| record.fields.get(pk).cloned().ok_or_else(|| {
+                DbError::ColumnNotFound(format!("Primary key {} not found", pk))
+            }))
+            .collect()
     }
 
     pub fn scan(&self) -> std::collections::btree_map::Values<'_ ,K, Record> {
         self.store.values()
     }
+
+    /// Records whose primary key lies between the bounds, in key order. Bounds that exclude
+    /// every key simply yield nothing.
+    pub fn range(&self, lower: Bound<K>, upper: Bound<K>) -> impl Iterator<Item = &Record> {
+        let empty = match (&lower, &upper) {
+            (Bound::Included(low), Bound::Included(high)) => low > high,
+            (Bound::Included(low) | Bound::Excluded(low), Bound::Included(high) | Bound::Excluded(high)) => low >= high,
+            _ => false,
+        };
+        (!empty).then(|| self.store.range((lower, upper)))
+            .into_iter()
+            .flatten()
+            .map(|(_, record)| record)
+    }
 }
 
 pub enum AnyDatabase {
     IntDatabase(Database<i64>),
     StringDatabase(Database<String>),
+    /// Each table is keyed by the type of its own primary-key column.
+    DynamicDatabase(Database<Key>),
 }
 
 impl AnyDatabase {
-    pub fn execute(&mut self, query: Query) -> DbResult<Option<String>> {
+    pub fn flush<P: AsRef<Path>>(&self, dir: P) -> DbResult<()> {
+        match self {
+            AnyDatabase::IntDatabase(database) => database.flush(dir),
+            AnyDatabase::StringDatabase(database) => database.flush(dir),
+            AnyDatabase::DynamicDatabase(database) => database.flush(dir),
+        }
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        match self {
+            AnyDatabase::IntDatabase(database) => database.in_transaction(),
+            AnyDatabase::StringDatabase(database) => database.in_transaction(),
+            AnyDatabase::DynamicDatabase(database) => database.in_transaction(),
+        }
+    }
+
+    pub fn register_function(&mut self, function: UserFunction) -> DbResult<()> {
+        match self {
+            AnyDatabase::IntDatabase(database) => database.register_function(function),
+            AnyDatabase::StringDatabase(database) => database.register_function(function),
+            AnyDatabase::DynamicDatabase(database) => database.register_function(function),
+        }
+    }
+
+    pub fn table_names(&self) -> Vec<&str> {
+        match self {
+            AnyDatabase::IntDatabase(database) => database.table_names(),
+            AnyDatabase::StringDatabase(database) => database.table_names(),
+            AnyDatabase::DynamicDatabase(database) => database.table_names(),
+        }
+    }
+
+    pub fn describe(&self, table: &str) -> DbResult<TableInfo> {
+        match self {
+            AnyDatabase::IntDatabase(database) => database.describe(table),
+            AnyDatabase::StringDatabase(database) => database.describe(table),
+            AnyDatabase::DynamicDatabase(database) => database.describe(table),
+        }
+    }
+
+    pub fn checkpoint(&mut self) -> DbResult<()> {
+        match self {
+            AnyDatabase::IntDatabase(database) => database.checkpoint(),
+            AnyDatabase::StringDatabase(database) => database.checkpoint(),
+            AnyDatabase::DynamicDatabase(database) => database.checkpoint(),
+        }
+    }
+
+    pub fn execute(&mut self, query: Query) -> DbResult<QueryResult> {
         match self {
-            AnyDatabase::IntDatabase(database) => run_generic_query(database, query),
-            AnyDatabase::StringDatabase(database) => run_generic_query(database, query),
+            AnyDatabase::IntDatabase(database) => database.execute(query),
+            AnyDatabase::StringDatabase(database) => database.execute(query),
+            AnyDatabase::DynamicDatabase(database) => database.execute(query),
         }
     }
 }
 
-fn run_generic_query<K: DatabaseKey>(database: &mut Database<K>, query: Query) -> DbResult<Option<String>> {
+fn run_generic_query<K: DatabaseKey>(database: &mut Database<K>, query: Query) -> DbResult<QueryResult> {
     match query {
         Query::Create { table, pk, columns} => {
-            let schema: HashMap<_, _> = columns.into_iter().collect();
-            let mut cmd = CreateTableCommand {database, name: table, pk_name: pk, schema};
+            let mut cmd = CreateTableCommand {database, name: table, pk_columns: pk, columns};
             cmd.execute()
         },
-        Query::Insert { table, values} => {
+        Query::Insert { table, rows, on_conflict } => {
             let table = database.get_table_mut(&table)?;
-            let record = Record {fields: values.into_iter().collect()};
-            let mut cmd = InsertCommand {table, record};
+            let records = rows.into_iter().map(|values| Record {fields: values.into_iter().collect()}).collect();
+            let mut cmd = InsertCommand {table, records, on_conflict};
             cmd.execute()
         },
-        Query::Select { table, fields, condition } => {
+        Query::InsertSelect { table, select, on_conflict } => {
+            let result = match run_generic_query(database, *select)? {
+                QueryResult::Rows(result) => result,
+                _ => return Err(DbError::InvalidCommand("INSERT ... SELECT needs a query returning rows".into())),
+            };
+            let records = result.rows.into_iter()
+                .map(|row| Record {fields: result.columns.iter().cloned().zip(row).collect()})
+                .collect();
+            let table = database.get_table_mut(&table)?;
+            let mut cmd = InsertCommand {table, records, on_conflict};
+            cmd.execute()
+        },
+        Query::Select { table, joins, fields, condition, group_by, having, order_by, limit, offset } => {
+            let table = database.get_table(&table)?;
+            let joins = joins.into_iter()
+                .map(|join| {
+                    let right = database.get_table(&join.table)?;
+                    Ok((join, right))
+                })
+                .collect::<DbResult<Vec<_>>>()?;
+            let mut cmd = SelectCommand {table, joins, fields, condition, group_by, having, order_by, limit, offset};
+            cmd.execute()
+        },
+        Query::Update { table, values, condition } => {
+            let table = database.get_table_mut(&table)?;
+            let mut cmd = UpdateCommand {table, values, condition};
+            cmd.execute()
+        },
+        Query::ShowTables => {
+            let mut cmd = ShowTablesCommand { database };
+            cmd.execute()
+        },
+        Query::Describe { table } => {
             let table = database.get_table(&table)?;
-            let mut cmd = SelectCommand {table, fields, condition};
+            let mut cmd = DescribeCommand { table };
+            cmd.execute()
+        },
+        Query::CreateIndex { name, table, column } => {
+            let mut cmd = CreateIndexCommand { database, name, table, column };
+            cmd.execute()
+        },
+        Query::DropIndex { name } => {
+            let mut cmd = DropIndexCommand { database, name };
+            cmd.execute()
+        },
+        Query::DropTable { table, if_exists } => {
+            let mut cmd = DropTableCommand { database, name: table, if_exists };
+            cmd.execute()
+        },
+        Query::Truncate { table } => {
+            let table = database.get_table_mut(&table)?;
+            let mut cmd = TruncateCommand { table };
+            cmd.execute()
+        },
+        Query::AlterTable { table, action } => {
+            let mut cmd = AlterTableCommand { database, table, action };
             cmd.execute()
         },
-        Query::Delete { table, key_value } => {
-            let key = K::from_value(&key_value).ok_or(DbError::KeyMismatch)?;
+        Query::Delete { table, key } => {
+            let key = K::from_values(&key).ok_or(DbError::KeyMismatch)?;
             let t = database.get_table_mut(&table)?;
             let mut cmd = DeleteCommand { table: t, key };
             cmd.execute()
         },
-        _ => Ok(None)
+        Query::DeleteWhere { table, condition } => {
+            let table = database.get_table_mut(&table)?;
+            let mut cmd = DeleteWhereCommand { table, condition };
+            cmd.execute()
+        },
+        Query::Begin | Query::Commit | Query::Rollback => {
+            Err(DbError::InvalidCommand("Transaction statements are handled by Database::execute".into()))
+        }
+        Query::SaveAs(_) | Query::ReadFrom { .. } => {
+            Err(DbError::InvalidCommand("SAVE_AS and READ_FROM are handled by the console".into()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Value;
+    use crate::queries::parse;
+
+    fn run<K: DatabaseKey>(db: &mut Database<K>, input: &str) -> DbResult<QueryResult> {
+        parse(input).and_then(|query| db.execute(query))
+    }
+
+    fn setup_db() -> Database<i64> {
+        let mut db = Database::new();
+        for input in ["CREATE people KEY id FIELDS id:Int, job:String", "INSERT id=1, job=\"actor\" INTO people"] {
+            if let Err(e) = run(&mut db, input) {
+                panic!("Setup '{}' failed: {:?}", input, e);
+            }
+        }
+        db
+    }
+
+    #[test]
+    fn test_rollback_restores_tables() {
+        let mut db = setup_db();
+        for input in [
+            "BEGIN",
+            "INSERT id=2, job=\"baker\" INTO people",
+            "UPDATE people SET job = \"cook\" WHERE id = 1",
+            "CREATE pets KEY id FIELDS id:Int",
+            "ROLLBACK",
+        ] {
+            if let Err(e) = run(&mut db, input) {
+                panic!("Executing '{}' failed: {:?}", input, e);
+            }
+        }
+
+        assert!(!db.in_transaction());
+        assert!(matches!(db.get_table("pets"), Err(DbError::TableNotFound(_))));
+        match db.get_table("people") {
+            Ok(table) => {
+                assert_eq!(table.store.len(), 1);
+                assert_eq!(table.store[&1].fields.get("job"), Some(&Value::String("actor".into())));
+            }
+            Err(e) => panic!("Table missing after rollback: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_commit_keeps_changes_after_failed_statement() {
+        let mut db = setup_db();
+        assert!(run(&mut db, "BEGIN").is_ok());
+        assert!(run(&mut db, "INSERT id=2, job=\"baker\" INTO people").is_ok());
+        assert!(matches!(run(&mut db, "INSERT id=1, job=\"cook\" INTO people"), Err(DbError::DuplicateKey)));
+        assert!(db.in_transaction(), "A failed statement should not end the transaction");
+        assert!(run(&mut db, "COMMIT").is_ok());
+
+        match db.get_table("people") {
+            Ok(table) => assert_eq!(table.store.keys().copied().collect::<Vec<_>>(), vec![1, 2]),
+            Err(e) => panic!("Table missing after commit: {:?}", e),
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_transaction_statements_out_of_order() {
+        let mut db = setup_db();
+        assert!(matches!(run(&mut db, "COMMIT"), Err(DbError::InvalidCommand(_))));
+        assert!(matches!(run(&mut db, "ROLLBACK"), Err(DbError::InvalidCommand(_))));
+        assert!(run(&mut db, "BEGIN").is_ok());
+        assert!(matches!(run(&mut db, "BEGIN"), Err(DbError::InvalidCommand(_))));
+    }
+
+    #[test]
+    fn test_insert_fills_defaults_and_auto_increment_keys() {
+        let mut db: Database<i64> = Database::new();
+        for input in [
+            "CREATE people KEY id FIELDS id:Int AUTO_INCREMENT, job:String DEFAULT \"none\", age:Int",
+            "INSERT age=30 INTO people",
+            "INSERT id=10, job=\"cook\" INTO people",
+            "INSERT age=NULL INTO people",
+        ] {
+            if let Err(e) = run(&mut db, input) {
+                panic!("Executing '{}' failed: {:?}", input, e);
+            }
+        }
+
+        match db.get_table("people") {
+            Ok(table) => {
+                assert_eq!(table.store.keys().copied().collect::<Vec<_>>(), vec![1, 10, 11]);
+                assert_eq!(table.store[&1].fields.get("job"), Some(&Value::String("none".into())));
+                assert_eq!(table.store[&10].fields.get("age"), Some(&Value::Null));
+                assert_eq!(table.store[&11].fields.get("job"), Some(&Value::String("none".into())));
+            }
+            Err(e) => panic!("Table missing: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_create_rejects_invalid_column_options() {
+        let mut db: Database<i64> = Database::new();
+        for input in [
+            "CREATE t KEY id FIELDS id:Int, age:Int DEFAULT \"old\"",
+            "CREATE t KEY id FIELDS id:Int, age:Int NOT NULL DEFAULT NULL",
+            "CREATE t KEY id FIELDS id:Int, age:Int AUTO_INCREMENT",
+            "CREATE t KEY id FIELDS id:String AUTO_INCREMENT",
+        ] {
+            assert!(run(&mut db, input).is_err(), "'{}' should have been rejected", input);
+        }
+        assert!(matches!(db.get_table("t"), Err(DbError::TableNotFound(_))));
+    }
+
+    #[test]
+    fn test_catalog_describes_tables() {
+        let mut db = setup_db();
+        for input in ["CREATE pets KEY name FIELDS name:Int, age:Int NOT NULL", "CREATE INDEX people_job ON people(job)"] {
+            if let Err(e) = run(&mut db, input) {
+                panic!("Executing '{}' failed: {:?}", input, e);
+            }
+        }
+
+        assert_eq!(db.table_names(), ["people", "pets"]);
+        match db.describe("people") {
+            Ok(info) => {
+                assert_eq!(info.pk_columns, ["id"]);
+                assert_eq!(info.row_count, 1);
+                assert_eq!(info.columns, [
+                    ColumnDef::new("id", DataType::Int).not_null(),
+                    ColumnDef::new("job", DataType::String),
+                ]);
+                assert_eq!(info.indexes, [IndexInfo { name: "people_job".into(), column: "job".into() }]);
+            }
+            Err(e) => panic!("DESCRIBE failed: {:?}", e),
+        }
+        assert!(matches!(db.describe("cars"), Err(DbError::TableNotFound(_))));
+    }
+
+    #[test]
+    fn test_alter_table_changes_schema_and_records() {
+        let mut db = setup_db();
+        for input in [
+            "CREATE INDEX people_job ON people(job)",
+            "ALTER TABLE people ADD COLUMN age:Int NOT NULL DEFAULT 18",
+            "ALTER TABLE people RENAME COLUMN job TO occupation",
+            "ALTER TABLE people RENAME COLUMN id TO person_id",
+            "ALTER TABLE people RENAME TO persons",
+            "INSERT person_id=2, occupation=\"baker\" INTO persons",
+        ] {
+            if let Err(e) = run(&mut db, input) {
+                panic!("Executing '{}' failed: {:?}", input, e);
+            }
+        }
+        assert!(matches!(db.get_table("people"), Err(DbError::TableNotFound(_))));
+        assert!(matches!(run(&mut db, "ALTER TABLE persons ADD note:String NOT NULL"), Err(DbError::NullViolation(_))));
+        assert!(matches!(run(&mut db, "ALTER TABLE persons DROP COLUMN person_id"), Err(DbError::InvalidCommand(_))));
+
+        match db.get_table("persons") {
+            Ok(table) => {
+                assert_eq!(table.columns(), ["person_id", "occupation", "age"]);
+                assert_eq!(table.pk_columns(), ["person_id"]);
+                assert_eq!(table.store[&1].fields.get("age"), Some(&Value::Int(18)));
+                assert_eq!(table.store[&2].fields.get("occupation"), Some(&Value::String("baker".into())));
+                assert!(table.index_on("occupation").is_some());
+            }
+            Err(e) => panic!("Table missing after ALTER: {:?}", e),
+        }
+
+        assert!(run(&mut db, "ALTER TABLE persons DROP COLUMN occupation").is_ok());
+        match db.get_table("persons") {
+            Ok(table) => {
+                assert_eq!(table.columns(), ["person_id", "age"]);
+                assert_eq!(table.store[&1].fields.get("occupation"), None);
+                assert!(table.indexes().is_empty());
+            }
+            Err(e) => panic!("Table missing after DROP COLUMN: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_drop_and_truncate_roll_back() {
+        let mut db = setup_db();
+        assert!(matches!(run(&mut db, "DROP TABLE pets"), Err(DbError::TableNotFound(_))));
+        assert!(run(&mut db, "DROP TABLE IF EXISTS pets").is_ok());
+
+        for input in ["BEGIN", "TRUNCATE people", "ALTER TABLE people RENAME TO persons", "DROP TABLE persons"] {
+            if let Err(e) = run(&mut db, input) {
+                panic!("Executing '{}' failed: {:?}", input, e);
+            }
+        }
+        assert!(db.get_table("persons").is_err() && db.get_table("people").is_err());
+        assert!(run(&mut db, "ROLLBACK").is_ok());
+
+        assert!(matches!(db.get_table("persons"), Err(DbError::TableNotFound(_))));
+        match db.get_table("people") {
+            Ok(table) => assert_eq!(table.store.len(), 1),
+            Err(e) => panic!("Table missing after rollback: {:?}", e),
+        }
+        assert!(matches!(run(&mut db, "TRUNCATE people"), Ok(QueryResult::RowsAffected(1))));
+    }
+
+    #[test]
+    fn test_index_names_are_unique_and_survive_rollback() {
+        let mut db = setup_db();
+        assert!(run(&mut db, "CREATE INDEX people_job ON people(job)").is_ok());
+        assert!(matches!(run(&mut db, "CREATE INDEX people_job ON people(id)"), Err(DbError::InvalidCommand(_))));
+        assert!(matches!(run(&mut db, "CREATE INDEX other ON people(salary)"), Err(DbError::ColumnNotFound(_))));
+
+        for input in ["BEGIN", "DROP INDEX people_job", "ROLLBACK"] {
+            if let Err(e) = run(&mut db, input) {
+                panic!("Executing '{}' failed: {:?}", input, e);
+            }
+        }
+        match db.get_table("people") {
+            Ok(table) => assert!(table.index_on("job").is_some(), "Rollback should restore the index"),
+            Err(e) => panic!("Table missing: {:?}", e),
+        }
+        assert!(run(&mut db, "DROP INDEX people_job").is_ok());
+        assert!(matches!(run(&mut db, "DROP INDEX people_job"), Err(DbError::InvalidCommand(_))));
+    }
+
+    #[test]
+    fn test_tables_choose_their_key_type() {
+        let mut db: Database<Key> = Database::new();
+        for input in [
+            "CREATE people KEY id FIELDS id:Int, job:String",
+            "CREATE jobs KEY title FIELDS title:String, pay:Int",
+            "INSERT id=10, job=\"cook\" INTO people",
+            "INSERT id=9, job=\"actor\" INTO people",
+            "INSERT title=\"cook\", pay=3 INTO jobs",
+            "DELETE 9 FROM people",
+            "DELETE \"cook\" FROM jobs",
+        ] {
+            if let Err(e) = run(&mut db, input) {
+                panic!("Executing '{}' failed: {:?}", input, e);
+            }
+        }
+        assert!(matches!(run(&mut db, "INSERT id=\"x\", job=\"cook\" INTO people"), Err(DbError::TypeMismatch(_))));
+        assert!(matches!(run(&mut db, "CREATE flags KEY on FIELDS on:Bool"), Err(DbError::TypeMismatch(_))));
+        assert!(matches!(run(&mut db, "CREATE prices KEY id FIELDS price:Float"), Err(DbError::ColumnNotFound(_))));
+
+        match (db.get_table("people"), db.get_table("jobs")) {
+            (Ok(people), Ok(jobs)) => {
+                assert_eq!(people.store.keys().collect::<Vec<_>>(), vec![&Key::Int(10)]);
+                assert!(jobs.store.is_empty());
+            }
+            other => panic!("Tables missing: {:?}", other.0.err()),
+        }
+
+        let mut typed = setup_db();
+        assert!(matches!(run(&mut typed, "CREATE jobs KEY title FIELDS title:String"), Err(DbError::TypeMismatch(_))));
+    }
+
+    #[test]
+    fn test_composite_keys_are_unique_and_ordered() {
+        let mut db: Database<Key> = Database::new();
+        for input in [
+            "CREATE visits KEY (person, day) FIELDS person:Int, day:String, note:String",
+            "INSERT person=2, day=\"mon\" INTO visits",
+            "INSERT person=1, day=\"tue\" INTO visits",
+            "INSERT person=1, day=\"mon\", note=\"first\" INTO visits",
+        ] {
+            if let Err(e) = run(&mut db, input) {
+                panic!("Executing '{}' failed: {:?}", input, e);
+            }
+        }
+        assert!(matches!(run(&mut db, "INSERT person=1, day=\"mon\" INTO visits"), Err(DbError::DuplicateKey)));
+        assert!(matches!(run(&mut db, "INSERT person=3 INTO visits"), Err(DbError::NullViolation(_))));
+        assert!(matches!(run(&mut db, "DELETE 1 FROM visits"), Err(DbError::KeyMismatch)));
+        assert!(matches!(run(&mut db, "ALTER TABLE visits DROP day"), Err(DbError::InvalidCommand(_))));
+
+        match run(&mut db, "SELECT note FROM visits") {
+            Ok(QueryResult::Rows(result)) => assert_eq!(result.rows[0], vec![Value::String("first".into())]),
+            other => panic!("Expected rows: {:?}", other),
+        }
+        assert!(run(&mut db, "DELETE (1, \"mon\") FROM visits").is_ok());
+        match db.get_table("visits") {
+            Ok(table) => {
+                let people: Vec<&Value> = table.scan().filter_map(|record| record.fields.get("person")).collect();
+                assert_eq!(people, [&Value::Int(1), &Value::Int(2)]);
+            }
+            Err(e) => panic!("Table missing: {:?}", e),
+        }
+
+        let mut typed = setup_db();
+        assert!(matches!(
+            run(&mut typed, "CREATE visits KEY (person, day) FIELDS person:Int, day:Int"),
+            Err(DbError::InvalidCommand(_))
+        ));
+        assert!(matches!(
+            run(&mut db, "CREATE pairs KEY (a, a) FIELDS a:Int"),
+            Err(DbError::InvalidCommand(_))
+        ));
+    }
+
+    #[test]
+    fn test_insert_many_is_all_or_nothing() {
+        let mut db = setup_db();
+        assert!(matches!(
+            run(&mut db, "INSERT (id=2, job=\"baker\"), (id=3, job=\"cook\") INTO people"),
+            Ok(QueryResult::RowsAffected(2))
+        ));
+        for input in [
+            "INSERT (id=4, job=\"pilot\"), (id=1, job=\"clown\") INTO people",
+            "INSERT (id=4, job=\"pilot\"), (id=4, job=\"clown\") INTO people",
+            "INSERT (id=4, job=\"pilot\"), (id=5, job=7) INTO people",
+        ] {
+            assert!(run(&mut db, input).is_err(), "'{}' should fail", input);
+        }
+        match db.get_table("people") {
+            Ok(table) => assert_eq!(table.store.keys().copied().collect::<Vec<_>>(), vec![1, 2, 3]),
+            Err(e) => panic!("Table missing: {:?}", e),
+        }
+
+        let mut numbered: Database<i64> = Database::new();
+        for input in [
+            "CREATE jobs KEY id FIELDS id:Int AUTO_INCREMENT, title:String",
+            "INSERT (title=\"a\"), (id=10, title=\"b\"), (title=\"c\") INTO jobs",
+        ] {
+            if let Err(e) = run(&mut numbered, input) {
+                panic!("Executing '{}' failed: {:?}", input, e);
+            }
+        }
+        match numbered.get_table("jobs") {
+            Ok(table) => assert_eq!(table.store.keys().copied().collect::<Vec<_>>(), vec![1, 10, 11]),
+            Err(e) => panic!("Table missing: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_insert_select_copies_rows() {
+        let mut db = setup_db();
+        for input in [
+            "INSERT (id=2, job=\"baker\"), (id=3, job=\"cook\") INTO people",
+            "CREATE archive KEY id FIELDS id:Int, job:String, note:String DEFAULT \"copied\"",
+        ] {
+            if let Err(e) = run(&mut db, input) {
+                panic!("Executing '{}' failed: {:?}", input, e);
+            }
+        }
+        assert!(matches!(
+            run(&mut db, "INSERT INTO archive SELECT id, job FROM people WHERE id > 1"),
+            Ok(QueryResult::RowsAffected(2))
+        ));
+        assert!(matches!(
+            run(&mut db, "INSERT INTO archive SELECT id, job FROM people"),
+            Err(DbError::DuplicateKey)
+        ));
+        assert!(matches!(
+            run(&mut db, "INSERT INTO archive SELECT id, job AS title FROM people WHERE id = 1"),
+            Err(DbError::ColumnNotFound(_))
+        ));
+        match db.get_table("archive") {
+            Ok(table) => {
+                assert_eq!(table.store.keys().copied().collect::<Vec<_>>(), vec![2, 3]);
+                assert_eq!(table.store[&3].fields.get("note"), Some(&Value::String("copied".into())));
+            }
+            Err(e) => panic!("Table missing: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_select_with_expressions() {
+        let mut db: Database<i64> = Database::new();
+        for input in [
+            "CREATE books KEY id FIELDS id:Int, title:String, pages:Int, rating:Float",
+            "INSERT (id=1, title=\"Dune\", pages=600, rating=4.5), (id=2, title=\"Emma\", pages=300, rating=1.0) \
+             INTO books",
+        ] {
+            if let Err(e) = run(&mut db, input) {
+                panic!("Executing '{}' failed: {:?}", input, e);
+            }
+        }
+        let input = "SELECT title || \"!\" AS loud, pages / 7, rating * 2 FROM books WHERE rating * 2 > pages / 100";
+        match run(&mut db, input) {
+            Ok(QueryResult::Rows(result)) => {
+                assert_eq!(result.columns, vec!["loud", "pages / 7", "rating * 2"]);
+                assert_eq!(result.types, vec![DataType::String, DataType::Int, DataType::Float]);
+                assert_eq!(result.rows, vec![vec![Value::String("Dune!".into()), Value::Int(85), Value::Float(9.0)]]);
+            }
+            other => panic!("Expected rows: {:?}", other),
+        }
+        assert!(matches!(run(&mut db, "SELECT pages / (id - 1) FROM books"), Err(DbError::DivisionByZero)));
+        assert!(matches!(run(&mut db, "SELECT id FROM books WHERE title > pages"), Err(DbError::TypeMismatch(_))));
+        for input in [
+            "UPDATE books SET pages = 5 WHERE title > 5",
+            "DELETE FROM books WHERE title > 5",
+            "SELECT title FROM books GROUP BY title HAVING MAX(title) > 5",
+        ] {
+            assert!(matches!(run(&mut db, input), Err(DbError::TypeMismatch(_))), "'{}' should be rejected", input);
+        }
+        match db.get_table("books") {
+            Ok(table) => {
+                assert_eq!(table.store.len(), 2);
+                assert_eq!(table.store[&1].fields.get("pages"), Some(&Value::Int(600)));
+            }
+            Err(e) => panic!("Table missing: {:?}", e),
+        }
+        let widened = run(&mut db, "SELECT id FROM books WHERE rating = 1");
+        assert!(matches!(widened, Ok(QueryResult::Rows(r)) if r.rows == vec![vec![Value::Int(2)]]));
+    }
+
+    #[test]
+    fn test_select_with_functions() {
+        let mut db: Database<i64> = Database::new();
+        let create = "CREATE books KEY id FIELDS id:Int, title:String, pages:Int, rating:Float";
+        if let Err(e) = run(&mut db, create) {
+            panic!("Executing '{}' failed: {:?}", create, e);
+        }
+        // Arguments are checked before any row is read, so even an empty table reports them.
+        for (input, mismatch) in [
+            ("SELECT UPPER(pages) FROM books", true),
+            ("SELECT id FROM books WHERE ABS(title) > 1", true),
+            ("SELECT COALESCE(title, pages) FROM books", true),
+            ("SELECT SUBSTR(title) FROM books", false),
+        ] {
+            match run(&mut db, input) {
+                Err(DbError::TypeMismatch(_)) if mismatch => {}
+                Err(DbError::InvalidCommand(_)) if !mismatch => {}
+                other => panic!("'{}' should have been rejected: {:?}", input, other),
+            }
+        }
+        assert!(matches!(run(&mut db, "SELECT FROBNICATE(id) FROM books"), Err(DbError::FunctionNotFound(_))));
+
+        let insert = "INSERT (id=1, title=\" dune \", pages=612, rating=4.25), (id=2, title=\"Emma\", pages=474) \
+                      INTO books";
+        if let Err(e) = run(&mut db, insert) {
+            panic!("Executing '{}' failed: {:?}", insert, e);
+        }
+        let input = "SELECT UPPER(TRIM(title)), ROUND(COALESCE(rating, 0), 1), \
+                     CASE WHEN pages > 500 THEN \"long\" ELSE \"short\" END FROM books WHERE LENGTH(TRIM(title)) = 4";
+        match run(&mut db, input) {
+            Ok(QueryResult::Rows(result)) => {
+                assert_eq!(result.columns[2], "CASE WHEN pages > 500 THEN \"long\" ELSE \"short\" END");
+                assert_eq!(result.types, vec![DataType::String, DataType::Float, DataType::String]);
+                assert_eq!(result.rows, vec![
+                    vec![Value::String("DUNE".into()), Value::Float(4.3), Value::String("long".into())],
+                    vec![Value::String("EMMA".into()), Value::Float(0.0), Value::String("short".into())],
+                ]);
+            }
+            other => panic!("Expected rows: {:?}", other),
+        }
+
+        let insert = "INSERT (id=3, title=\"Ulysses\", pages=730, rating=9.5) INTO books";
+        if let Err(e) = run(&mut db, insert) {
+            panic!("Executing '{}' failed: {:?}", insert, e);
+        }
+        match run(&mut db, "SELECT id FROM books ORDER BY COALESCE(rating, 5)") {
+            Ok(QueryResult::Rows(result)) => {
+                assert_eq!(result.rows, vec![vec![Value::Int(1)], vec![Value::Int(2)], vec![Value::Int(3)]]);
+            }
+            other => panic!("Expected rows: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_user_defined_functions() {
+        let mut db = setup_db();
+        let initials = UserFunction::new("initials", 1, DataType::String, |args| match args {
+            [Value::String(s)] => Ok(Value::String(s.split(' ').filter_map(|w| w.chars().next()).collect())),
+            _ => Ok(Value::Null),
+        });
+        let is_odd = UserFunction::new("is_odd", 1, DataType::Bool, |args| match args {
+            [Value::Int(n)] => Ok(Value::Bool(n % 2 != 0)),
+            _ => Ok(Value::Null),
+        });
+        let broken = UserFunction::new("broken", 0, DataType::Int, |_| Ok(Value::String("oops".into())));
+        for function in [initials, is_odd, broken] {
+            if let Err(e) = db.register_function(function) {
+                panic!("Registering a function failed: {:?}", e);
+            }
+        }
+        let rejected = [
+            UserFunction::new("UPPER", 1, DataType::String, |_| Ok(Value::Null)),
+            UserFunction::new("Is_Odd", 1, DataType::Bool, |_| Ok(Value::Null)),
+            UserFunction::new("no way", 1, DataType::Bool, |_| Ok(Value::Null)),
+        ];
+        for function in rejected {
+            assert!(matches!(db.register_function(function), Err(DbError::InvalidCommand(_))));
+        }
+
+        if let Err(e) = run(&mut db, "INSERT (id=2, job=\"bread baker\"), (id=3, job=\"cook\") INTO people") {
+            panic!("Insert failed: {:?}", e);
+        }
+        match run(&mut db, "SELECT id, INITIALS(job) FROM people WHERE is_odd(id) = true") {
+            Ok(QueryResult::Rows(result)) => {
+                assert_eq!(result.columns, vec!["id", "initials(job)"]);
+                assert_eq!(result.types, vec![DataType::Int, DataType::String]);
+                assert_eq!(result.rows, vec![
+                    vec![Value::Int(1), Value::String("a".into())],
+                    vec![Value::Int(3), Value::String("c".into())],
+                ]);
+            }
+            other => panic!("Expected rows: {:?}", other),
+        }
+        let deleted = run(&mut db, "DELETE FROM people WHERE is_odd(id) = false");
+        assert!(matches!(deleted, Ok(QueryResult::RowsAffected(1))));
+        assert!(matches!(run(&mut db, "SELECT is_odd(id, 2) FROM people"), Err(DbError::InvalidCommand(_))));
+        assert!(matches!(run(&mut db, "SELECT broken() FROM people"), Err(DbError::TypeMismatch(_))));
+    }
+
+    #[test]
+    fn test_upsert_reports_each_row() {
+        let mut db = setup_db();
+        let actions = |result: DbResult<QueryResult>| match result {
+            Ok(QueryResult::Rows(result)) => result.rows,
+            other => panic!("Expected rows: {:?}", other),
+        };
+        let row = |id: i64, action: &str| vec![Value::Int(id), Value::String(action.into())];
+
+        let input = "INSERT (id=1, job=\"clown\"), (id=2, job=\"baker\") INTO people ON CONFLICT DO NOTHING";
+        let rows = actions(run(&mut db, input));
+        assert_eq!(rows, vec![row(1, "skipped"), row(2, "inserted")]);
+        let rows = actions(run(&mut db, "INSERT (id=2), (id=3) INTO people ON CONFLICT DO UPDATE SET job = \"cook\""));
+        assert_eq!(rows, vec![row(2, "updated"), row(3, "inserted")]);
+        let rows = actions(run(&mut db, "REPLACE id=1 INTO people"));
+        assert_eq!(rows, vec![row(1, "updated")]);
+
+        assert!(matches!(
+            run(&mut db, "INSERT id=3 INTO people ON CONFLICT DO UPDATE SET id = 4"),
+            Err(DbError::InvalidCommand(_))
+        ));
+        assert!(matches!(
+            run(&mut db, "INSERT (id=5), (id=3) INTO people ON CONFLICT DO UPDATE SET job = 7"),
+            Err(DbError::TypeMismatch(_))
+        ));
+        match db.get_table("people") {
+            Ok(table) => {
+                let jobs: Vec<Option<&Value>> = table.scan().map(|record| record.fields.get("job")).collect();
+                let cook = Value::String("cook".into());
+                assert_eq!(jobs, vec![Some(&Value::Null), Some(&cook), Some(&Value::Null)]);
+            }
+            Err(e) => panic!("Table missing: {:?}", e),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt;
 use crate::error::{DbError, DbResult};
@@ -8,6 +9,7 @@ pub enum Value {
     String(String),
     Bool(bool),
     Float(f64),
+    Null,
 }
 
 impl fmt::Display for Value {
@@ -17,10 +19,58 @@ impl fmt::Display for Value {
             Value::String(s) => write!(f, "{}", s),
             Value::Bool(b) => write!(f, "{}", b),
             Value::Float(fl) => write!(f, "{}", fl),
+            Value::Null => write!(f, "NULL"),
         }
     }
 }
 
+impl Value {
+    /// Orders any two values. Unlike `partial_cmp` it never gives up: floats use their total
+    /// order, except that `0.0` and `-0.0` stay equal as they are for `==`.
+    pub fn total_cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Float(a), Value::Float(b)) if a == b => Ordering::Equal,
+            (Value::Float(a), Value::Float(b)) => a.total_cmp(b),
+            _ => self.partial_cmp(other).unwrap_or(Ordering::Equal),
+        }
+    }
+
+    /// Type of the value, or `None` for NULL, which fits any column.
+    pub fn data_type(&self) -> Option<DataType> {
+        match self {
+            Value::Int(_) => Some(DataType::Int),
+            Value::String(_) => Some(DataType::String),
+            Value::Bool(_) => Some(DataType::Bool),
+            Value::Float(_) => Some(DataType::Float),
+            Value::Null => None,
+        }
+    }
+}
+
+/// A value that can be used as a map key, ordered by [`Value::total_cmp`].
+#[derive(Debug, Clone)]
+pub struct IndexKey(pub Value);
+
+impl PartialEq for IndexKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for IndexKey {}
+
+impl PartialOrd for IndexKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IndexKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum DataType {
     Bool,
@@ -29,7 +79,76 @@ pub enum DataType {
     String,
 }
 
-#[derive(Debug, Clone)]
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DataType::Bool => "Bool",
+            DataType::Int => "Int",
+            DataType::Float => "Float",
+            DataType::String => "String",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A column as declared in CREATE. Columns are nullable unless declared `NOT NULL`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDef {
+    pub name: String,
+    pub data_type: DataType,
+    pub nullable: bool,
+    /// Value used when INSERT does not mention the column.
+    pub default: Option<Value>,
+    /// Only valid on a single-column Int primary key: a missing key is set to one more than
+    /// the largest key in the table.
+    pub auto_increment: bool,
+}
+
+impl ColumnDef {
+    pub fn new(name: impl Into<String>, data_type: DataType) -> ColumnDef {
+        ColumnDef { name: name.into(), data_type, nullable: true, default: None, auto_increment: false }
+    }
+
+    pub fn not_null(mut self) -> ColumnDef {
+        self.nullable = false;
+        self
+    }
+
+    pub fn default(mut self, value: Value) -> ColumnDef {
+        self.default = Some(value);
+        self
+    }
+
+    pub fn auto_increment(mut self) -> ColumnDef {
+        self.auto_increment = true;
+        self
+    }
+
+    /// Checks that the default fits the column and that only a single-column Int primary key
+    /// is auto-incremented.
+    pub fn check(&self, pk_columns: &[String]) -> DbResult<()> {
+        if let Some(default) = &self.default {
+            Record::check_type(default, &self.data_type)?;
+            if *default == Value::Null && (!self.nullable || pk_columns.contains(&self.name)) {
+                return Err(DbError::NullViolation(self.name.clone()));
+            }
+        }
+        if self.auto_increment && (pk_columns != [self.name.as_str()] || self.data_type != DataType::Int) {
+            return Err(DbError::InvalidCommand(format!(
+                "AUTO_INCREMENT column '{}' must be an Int primary key", self.name
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl From<(String, DataType)> for ColumnDef {
+    fn from((name, data_type): (String, DataType)) -> Self {
+        ColumnDef::new(name, data_type)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Record {
     pub fields: HashMap<String, Value>,
 }
@@ -42,17 +161,21 @@ impl Record {
                 None => return Err(DbError::ColumnNotFound(col_name.clone()))
             }
         }
+        if let Some(extra) = self.fields.keys().find(|column| !schema.contains_key(*column)) {
+            return Err(DbError::ColumnNotFound(extra.clone()));
+        }
         Ok(())
     }
 
-    fn check_type(val: &Value, col_type: &DataType) -> DbResult<()> {
-        let valid = match (val, col_type) {
-            (Value::Bool(_), DataType::Bool) => true,
-            (Value::Int(_), DataType::Int) => true,
-            (Value::String(_), DataType::String) => true,
-            (Value::Float(_), DataType::Float) => true,
-            _ => false,
-        };
+    pub fn check_type(val: &Value, col_type: &DataType) -> DbResult<()> {
+        let valid = matches!(
+            (val, col_type),
+            (Value::Null, _)
+                | (Value::Bool(_), DataType::Bool)
+                | (Value::Int(_), DataType::Int)
+                | (Value::String(_), DataType::String)
+                | (Value::Float(_), DataType::Float)
+        );
         if valid {
             Ok(())
         } else {
@@ -63,6 +186,25 @@ impl Record {
 
 pub trait DatabaseKey: Ord + Clone + fmt::Debug {
     fn from_value(val: &Value) -> Option<Self>;
+
+    /// Builds a key from the values of all primary-key columns, in key order. Only
+    /// single-column keys are supported unless [`DatabaseKey::supports_composite`] says otherwise.
+    fn from_values(values: &[Value]) -> Option<Self> {
+        match values {
+            [value] => Self::from_value(value),
+            _ => None,
+        }
+    }
+
+    /// Whether a primary-key column of the given type can be stored under this key type.
+    fn supports(_data_type: &DataType) -> bool {
+        true
+    }
+
+    /// Whether a primary key may span several columns.
+    fn supports_composite() -> bool {
+        false
+    }
 }
 
 impl DatabaseKey for i64 {
@@ -73,6 +215,10 @@ impl DatabaseKey for i64 {
             None
         }
     }
+
+    fn supports(data_type: &DataType) -> bool {
+        *data_type == DataType::Int
+    }
 }
 
 impl DatabaseKey for String {
@@ -83,6 +229,59 @@ impl DatabaseKey for String {
             None
         }
     }
+
+    fn supports(data_type: &DataType) -> bool {
+        *data_type == DataType::String
+    }
+}
+
+/// A primary key whose type follows the key column of each table, so one database can hold
+/// tables keyed by `Int` next to tables keyed by `String`. Bools and floats are not accepted as keys.
+/// Composite keys are tuples of their parts and are ordered lexicographically.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Key {
+    Int(i64),
+    String(String),
+    Tuple(Vec<Key>),
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Int(n) => write!(f, "{}", n),
+            Key::String(s) => write!(f, "{}", s),
+            Key::Tuple(parts) => {
+                let parts: Vec<String> = parts.iter().map(|part| part.to_string()).collect();
+                write!(f, "({})", parts.join(", "))
+            }
+        }
+    }
+}
+
+impl DatabaseKey for Key {
+    fn from_value(val: &Value) -> Option<Self> {
+        match val {
+            Value::Int(n) => Some(Key::Int(*n)),
+            Value::String(s) => Some(Key::String(s.clone())),
+            _ => None,
+        }
+    }
+
+    fn from_values(values: &[Value]) -> Option<Self> {
+        match values {
+            [] => None,
+            [value] => Key::from_value(value),
+            _ => values.iter().map(Key::from_value).collect::<Option<Vec<_>>>().map(Key::Tuple),
+        }
+    }
+
+    fn supports(data_type: &DataType) -> bool {
+        matches!(data_type, DataType::Int | DataType::String)
+    }
+
+    fn supports_composite() -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
@@ -102,9 +301,54 @@ mod domain_tests {
         let result = record.validate(&schema);
 
         match result {
-            Err(DbError::TypeMismatch(_)) => assert!(true),
-            Ok(_) => assert!(false, "Check_type should reject String in Int column"),
-            Err(e) => assert!(false, "Expected TypeMismatch, got: {:?}", e),
+            Err(DbError::TypeMismatch(_)) => {}
+            Ok(_) => panic!("Check_type should reject String in Int column"),
+            Err(e) => panic!("Expected TypeMismatch, got: {:?}", e),
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_record_validation_rejects_unknown_column() {
+        let schema = HashMap::from([("col_a".to_string(), DataType::Int)]);
+        let fields = HashMap::from([
+            ("col_a".to_string(), Value::Int(1)),
+            ("col_b".to_string(), Value::Int(2)),
+        ]);
+        assert!(matches!(Record { fields }.validate(&schema), Err(DbError::ColumnNotFound(column)) if column == "col_b"));
+    }
+
+    #[test]
+    fn test_index_key_orders_floats() {
+        let mut keys = vec![
+            IndexKey(Value::Float(f64::NAN)),
+            IndexKey(Value::Float(2.5)),
+            IndexKey(Value::Float(-1.0)),
+        ];
+        keys.sort();
+        let values: Vec<Value> = keys.into_iter().map(|key| key.0).collect();
+        assert_eq!(values[..2], [Value::Float(-1.0), Value::Float(2.5)]);
+        assert!(matches!(values[2], Value::Float(f) if f.is_nan()));
+        assert_eq!(IndexKey(Value::Float(0.0)), IndexKey(Value::Float(-0.0)));
+    }
+
+    #[test]
+    fn test_key_follows_value_type() {
+        assert_eq!(Key::from_value(&Value::Int(7)), Some(Key::Int(7)));
+        assert_eq!(Key::from_value(&Value::String("a".into())), Some(Key::String("a".into())));
+        assert_eq!(Key::from_value(&Value::Float(1.0)), None);
+        assert_eq!(Key::from_value(&Value::Null), None);
+        assert!(Key::supports(&DataType::String) && !Key::supports(&DataType::Bool));
+        assert!(i64::supports(&DataType::Int) && !String::supports(&DataType::Int));
+    }
+
+    #[test]
+    fn test_composite_keys_order_lexicographically() {
+        let key = |a: i64, b: &str| Key::from_values(&[Value::Int(a), Value::String(b.into())]);
+        let mut keys = vec![key(2, "a"), key(1, "b"), key(1, "a")];
+        keys.sort();
+        assert_eq!(keys, vec![key(1, "a"), key(1, "b"), key(2, "a")]);
+        assert_eq!(key(1, "a"), Some(Key::Tuple(vec![Key::Int(1), Key::String("a".into())])));
+        assert_eq!(Key::from_values(&[Value::Int(1), Value::Null]), None);
+        assert_eq!(i64::from_values(&[Value::Int(1), Value::Int(2)]), None);
+    }
+}
@@ -8,6 +8,8 @@ pub enum DbError {
     TableAlreadyExists(String),
     #[error("Column '{0}' not found.")]
     ColumnNotFound(String),
+    #[error("Function '{0}' not found.")]
+    FunctionNotFound(String),
     #[error("Type mismatch: {0}")]
     TypeMismatch(String),
     #[error("Invalid command: {0}")]
@@ -16,6 +18,12 @@ pub enum DbError {
     KeyMismatch,
     #[error("Duplicate key")]
     DuplicateKey,
+    #[error("Column '{0}' cannot be NULL.")]
+    NullViolation(String),
+    #[error("Division by zero")]
+    DivisionByZero,
+    #[error("Integer overflow in {0}")]
+    Overflow(String),
     #[error("Syntax error: {0}")]
     SyntaxError(String),
     #[error("I/O error: {0}")]
@@ -24,6 +32,8 @@ pub enum DbError {
     CommandError(String),
     #[error("Invalid path: {0}")]
     InvalidPath(String),
+    #[error("Storage error: {0}")]
+    StorageError(String),
 }
 
 pub type DbResult<T> = Result<T, DbError>;
\ No newline at end of file
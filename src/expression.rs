@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use crate::commands::{Aggregate, Condition};
+use crate::domain::{DataType, Record, Value};
+use crate::error::{DbError, DbResult};
+use crate::functions::{self, builtin, UserFunction, UserFunctions};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Concat,
+}
+
+impl BinaryOperator {
+    /// Binding strength; `||` binds loosest, like in SQL.
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOperator::Concat => 1,
+            BinaryOperator::Add | BinaryOperator::Subtract => 2,
+            BinaryOperator::Multiply | BinaryOperator::Divide | BinaryOperator::Remainder => 3,
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Remainder => "%",
+            BinaryOperator::Concat => "||",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+/// A scalar expression over the columns of a row, such as `rating * 2` or `name || "!"`.
+///
+/// Any operation on NULL yields NULL. Arithmetic on two Ints stays Int, with `/` and `%`
+/// truncating; mixing an Int with a Float widens the Int to Float.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Column(String),
+    /// An aggregate of a grouped row, stored in the row under its display name.
+    Aggregate(Aggregate),
+    Negate(Box<Expr>),
+    Binary {
+        left: Box<Expr>,
+        operator: BinaryOperator,
+        right: Box<Expr>,
+    },
+    /// A call of a scalar function such as `UPPER(title)`, resolved by name when evaluated.
+    Function {
+        name: String,
+        args: Vec<Expr>,
+    },
+    /// A call of a function registered on the database, bound by [`Expr::bind`].
+    UserFunction {
+        function: Arc<UserFunction>,
+        args: Vec<Expr>,
+    },
+    /// `CASE WHEN condition THEN result ... ELSE otherwise END`; the first branch whose
+    /// condition holds wins, and without ELSE no match gives NULL.
+    Case {
+        branches: Vec<(Condition, Expr)>,
+        otherwise: Option<Box<Expr>>,
+    },
+    Cast {
+        expr: Box<Expr>,
+        data_type: DataType,
+    },
+}
+
+impl Expr {
+    pub fn binary(left: Expr, operator: BinaryOperator, right: Expr) -> Expr {
+        Expr::Binary { left: Box::new(left), operator, right: Box::new(right) }
+    }
+
+    pub fn eval(&self, record: &Record) -> DbResult<Value> {
+        match self {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Column(column) => record.fields.get(column).cloned()
+                .ok_or_else(|| DbError::ColumnNotFound(column.clone())),
+            Expr::Aggregate(aggregate) => {
+                let name = aggregate.to_string();
+                record.fields.get(&name).cloned().ok_or(DbError::ColumnNotFound(name))
+            }
+            Expr::Negate(inner) => match inner.eval(record)? {
+                Value::Null => Ok(Value::Null),
+                Value::Int(n) => n.checked_neg().map(Value::Int)
+                    .ok_or_else(|| DbError::Overflow(self.to_string())),
+                Value::Float(f) => Ok(Value::Float(-f)),
+                other => Err(DbError::TypeMismatch(format!("Cannot negate {}", other))),
+            },
+            Expr::Binary { left, operator, right } => {
+                let (left, right) = (left.eval(record)?, right.eval(record)?);
+                self.apply(*operator, left, right)
+            }
+            Expr::Function { name, args } => {
+                let function = builtin(name).ok_or_else(|| DbError::FunctionNotFound(name.clone()))?;
+                let args = args.iter().map(|arg| arg.eval(record)).collect::<DbResult<Vec<Value>>>()?;
+                function.call(args)
+            }
+            Expr::UserFunction { function, args } => {
+                let args = args.iter().map(|arg| arg.eval(record)).collect::<DbResult<Vec<Value>>>()?;
+                function.call(&args)
+            }
+            Expr::Case { branches, otherwise } => {
+                for (condition, result) in branches {
+                    if condition.evaluate(record)? {
+                        return result.eval(record);
+                    }
+                }
+                otherwise.as_ref().map_or(Ok(Value::Null), |otherwise| otherwise.eval(record))
+            }
+            Expr::Cast { expr, data_type } => functions::cast(expr.eval(record)?, data_type),
+        }
+    }
+
+    fn apply(&self, operator: BinaryOperator, left: Value, right: Value) -> DbResult<Value> {
+        let overflow = || DbError::Overflow(self.to_string());
+        match (operator, left, right) {
+            (_, Value::Null, _) | (_, _, Value::Null) => Ok(Value::Null),
+            (BinaryOperator::Concat, Value::String(a), Value::String(b)) => Ok(Value::String(a + &b)),
+            (BinaryOperator::Concat, a, b) => {
+                Err(DbError::TypeMismatch(format!("Cannot concatenate {} and {}", a, b)))
+            }
+            (BinaryOperator::Divide | BinaryOperator::Remainder, Value::Int(_), Value::Int(0)) => {
+                Err(DbError::DivisionByZero)
+            }
+            (_, Value::Int(a), Value::Int(b)) => match operator {
+                BinaryOperator::Add => a.checked_add(b),
+                BinaryOperator::Subtract => a.checked_sub(b),
+                BinaryOperator::Multiply => a.checked_mul(b),
+                BinaryOperator::Divide => a.checked_div(b),
+                BinaryOperator::Remainder => a.checked_rem(b),
+                BinaryOperator::Concat => unreachable!("concatenation is handled above"),
+            }.map(Value::Int).ok_or_else(overflow),
+            (_, a, b) => {
+                let (Some(a), Some(b)) = (as_float(&a), as_float(&b)) else {
+                    return Err(DbError::TypeMismatch(format!("Cannot apply {} to {} and {}", operator, a, b)));
+                };
+                if b == 0.0 && matches!(operator, BinaryOperator::Divide | BinaryOperator::Remainder) {
+                    return Err(DbError::DivisionByZero);
+                }
+                Ok(Value::Float(match operator {
+                    BinaryOperator::Add => a + b,
+                    BinaryOperator::Subtract => a - b,
+                    BinaryOperator::Multiply => a * b,
+                    BinaryOperator::Divide => a / b,
+                    BinaryOperator::Remainder => a % b,
+                    BinaryOperator::Concat => unreachable!("concatenation is handled above"),
+                }))
+            }
+        }
+    }
+
+    /// Checks the expression against the available columns and returns the type of its
+    /// result, or `None` when it can only be NULL.
+    pub fn data_type(&self, columns: &HashMap<String, DataType>) -> DbResult<Option<DataType>> {
+        match self {
+            Expr::Literal(value) => Ok(value.data_type()),
+            Expr::Column(column) => columns.get(column).cloned().map(Some)
+                .ok_or_else(|| DbError::ColumnNotFound(column.clone())),
+            Expr::Aggregate(aggregate) => {
+                aggregate.check(columns)?;
+                Ok(Some(aggregate.data_type(columns)))
+            }
+            Expr::Negate(inner) => match inner.data_type(columns)? {
+                data_type @ (None | Some(DataType::Int | DataType::Float)) => Ok(data_type),
+                Some(other) => Err(DbError::TypeMismatch(format!("Cannot negate {} in {}", other, self))),
+            },
+            Expr::Binary { left, operator, right } => {
+                let types = (left.data_type(columns)?, right.data_type(columns)?);
+                let mismatch = || {
+                    DbError::TypeMismatch(format!("Cannot apply {} to {} and {} in {}",
+                        operator, type_name(&types.0), type_name(&types.1), self))
+                };
+                if *operator == BinaryOperator::Concat {
+                    return match types {
+                        (None | Some(DataType::String), None | Some(DataType::String)) => Ok(Some(DataType::String)),
+                        _ => Err(mismatch()),
+                    };
+                }
+                match types {
+                    (None, None) => Ok(None),
+                    (Some(DataType::Int), None | Some(DataType::Int)) | (None, Some(DataType::Int)) => {
+                        Ok(Some(DataType::Int))
+                    }
+                    (None | Some(DataType::Int | DataType::Float), None | Some(DataType::Int | DataType::Float)) => {
+                        Ok(Some(DataType::Float))
+                    }
+                    _ => Err(mismatch()),
+                }
+            }
+            Expr::Function { name, args } => {
+                let function = builtin(name).ok_or_else(|| DbError::FunctionNotFound(name.clone()))?;
+                let types = args.iter().map(|arg| arg.data_type(columns)).collect::<DbResult<Vec<_>>>()?;
+                function.return_type(&types)
+            }
+            Expr::UserFunction { function, args } => {
+                function.check_arity(args.len())?;
+                for arg in args {
+                    arg.data_type(columns)?;
+                }
+                Ok(Some(function.return_type.clone()))
+            }
+            Expr::Case { branches, otherwise } => {
+                let mut types = Vec::with_capacity(branches.len() + 1);
+                for (condition, result) in branches {
+                    condition.check(columns)?;
+                    types.push(result.data_type(columns)?);
+                }
+                if let Some(otherwise) = otherwise {
+                    types.push(otherwise.data_type(columns)?);
+                }
+                functions::common_type(&types)
+            }
+            Expr::Cast { expr, data_type } => {
+                expr.data_type(columns)?;
+                Ok(Some(data_type.clone()))
+            }
+        }
+    }
+
+    /// Aggregates the expression reads.
+    pub fn aggregates(&self) -> Vec<&Aggregate> {
+        match self {
+            Expr::Literal(_) | Expr::Column(_) => Vec::new(),
+            Expr::Aggregate(aggregate) => vec![aggregate],
+            Expr::Negate(inner) | Expr::Cast { expr: inner, .. } => inner.aggregates(),
+            Expr::Binary { left, right, .. } => {
+                let mut aggregates = left.aggregates();
+                aggregates.extend(right.aggregates());
+                aggregates
+            }
+            Expr::Function { args, .. } | Expr::UserFunction { args, .. } => {
+                args.iter().flat_map(|arg| arg.aggregates()).collect()
+            }
+            Expr::Case { branches, otherwise } => branches.iter()
+                .flat_map(|(condition, result)| condition.aggregates().into_iter().chain(result.aggregates()))
+                .chain(otherwise.iter().flat_map(|otherwise| otherwise.aggregates()))
+                .collect(),
+        }
+    }
+
+    /// Columns the expression reads outside of aggregates.
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            Expr::Literal(_) | Expr::Aggregate(_) => Vec::new(),
+            Expr::Column(column) => vec![column],
+            Expr::Negate(inner) | Expr::Cast { expr: inner, .. } => inner.columns(),
+            Expr::Binary { left, right, .. } => {
+                let mut columns = left.columns();
+                columns.extend(right.columns());
+                columns
+            }
+            Expr::Function { args, .. } | Expr::UserFunction { args, .. } => {
+                args.iter().flat_map(|arg| arg.columns()).collect()
+            }
+            Expr::Case { branches, otherwise } => branches.iter()
+                .flat_map(|(condition, result)| condition.columns().into_iter().chain(result.columns()))
+                .chain(otherwise.iter().flat_map(|otherwise| otherwise.columns()))
+                .collect(),
+        }
+    }
+
+    /// Makes calls of the given user-defined functions refer to them. Other names are left
+    /// for the built-in functions.
+    pub fn bind(&mut self, functions: &UserFunctions) {
+        match self {
+            Expr::Literal(_) | Expr::Column(_) | Expr::Aggregate(_) => {}
+            Expr::Negate(inner) | Expr::Cast { expr: inner, .. } => inner.bind(functions),
+            Expr::Binary { left, right, .. } => {
+                left.bind(functions);
+                right.bind(functions);
+            }
+            Expr::Function { name, args } => {
+                args.iter_mut().for_each(|arg| arg.bind(functions));
+                if let Some(function) = functions.get(&name.to_uppercase()) {
+                    *self = Expr::UserFunction { function: function.clone(), args: std::mem::take(args) };
+                }
+            }
+            Expr::UserFunction { args, .. } => args.iter_mut().for_each(|arg| arg.bind(functions)),
+            Expr::Case { branches, otherwise } => {
+                for (condition, result) in branches {
+                    condition.bind(functions);
+                    result.bind(functions);
+                }
+                if let Some(otherwise) = otherwise {
+                    otherwise.bind(functions);
+                }
+            }
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Binary { operator, .. } => operator.precedence(),
+            _ => u8::MAX,
+        }
+    }
+}
+
+fn as_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Int(n) => Some(*n as f64),
+        Value::Float(f) => Some(*f),
+        _ => None,
+    }
+}
+
+fn type_name(data_type: &Option<DataType>) -> String {
+    data_type.as_ref().map_or("NULL".to_string(), |data_type| data_type.to_string())
+}
+
+fn write_call(f: &mut fmt::Formatter<'_>, name: &str, args: &[Expr]) -> fmt::Result {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    write!(f, "{}({})", name, args.join(", "))
+}
+
+/// Writes the expression back in query syntax; it also names unaliased select list entries.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Literal(Value::String(s)) => write!(f, "\"{}\"", s),
+            Expr::Literal(value) => write!(f, "{}", value),
+            Expr::Column(column) => write!(f, "{}", column),
+            Expr::Aggregate(aggregate) => write!(f, "{}", aggregate),
+            Expr::Negate(inner) if inner.precedence() < u8::MAX => write!(f, "-({})", inner),
+            Expr::Negate(inner) => write!(f, "-{}", inner),
+            Expr::Binary { left, operator, right } => {
+                // Operators are left-associative, so an equally strong right operand needs parentheses.
+                if left.precedence() < operator.precedence() {
+                    write!(f, "({})", left)?;
+                } else {
+                    write!(f, "{}", left)?;
+                }
+                write!(f, " {} ", operator)?;
+                if right.precedence() <= operator.precedence() {
+                    write!(f, "({})", right)
+                } else {
+                    write!(f, "{}", right)
+                }
+            }
+            Expr::Function { name, args } => write_call(f, name, args),
+            Expr::UserFunction { function, args } => write_call(f, &function.name, args),
+            Expr::Case { branches, otherwise } => {
+                write!(f, "CASE")?;
+                for (condition, result) in branches {
+                    write!(f, " WHEN {} THEN {}", condition, result)?;
+                }
+                if let Some(otherwise) = otherwise {
+                    write!(f, " ELSE {}", otherwise)?;
+                }
+                write!(f, " END")
+            }
+            Expr::Cast { expr, data_type } => write!(f, "CAST({} AS {})", expr, data_type),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> Record {
+        Record {
+            fields: HashMap::from([
+                ("pages".to_string(), Value::Int(300)),
+                ("rating".to_string(), Value::Float(4.5)),
+                ("title".to_string(), Value::String("Dune".into())),
+                ("note".to_string(), Value::Null),
+            ]),
+        }
+    }
+
+    fn column(name: &str) -> Expr {
+        Expr::Column(name.into())
+    }
+
+    fn int(n: i64) -> Expr {
+        Expr::Literal(Value::Int(n))
+    }
+
+    #[test]
+    fn test_eval_promotes_and_propagates_null() {
+        let cases = [
+            (Expr::binary(column("pages"), BinaryOperator::Divide, int(7)), Value::Int(42)),
+            (Expr::binary(column("pages"), BinaryOperator::Remainder, int(7)), Value::Int(6)),
+            (Expr::binary(column("rating"), BinaryOperator::Multiply, int(2)), Value::Float(9.0)),
+            (Expr::binary(column("pages"), BinaryOperator::Add, column("note")), Value::Null),
+            (Expr::Negate(Box::new(column("pages"))), Value::Int(-300)),
+            (
+                Expr::binary(column("title"), BinaryOperator::Concat, Expr::Literal(Value::String("!".into()))),
+                Value::String("Dune!".into()),
+            ),
+        ];
+        for (expr, expected) in cases {
+            match expr.eval(&record()) {
+                Ok(value) => assert_eq!(value, expected, "{}", expr),
+                Err(e) => panic!("Evaluating {} failed: {:?}", expr, e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        let by_zero = Expr::binary(column("pages"), BinaryOperator::Divide, int(0));
+        assert!(matches!(by_zero.eval(&record()), Err(DbError::DivisionByZero)));
+        let by_zero = Expr::binary(column("rating"), BinaryOperator::Remainder, Expr::Literal(Value::Float(0.0)));
+        assert!(matches!(by_zero.eval(&record()), Err(DbError::DivisionByZero)));
+        let overflow = Expr::binary(int(i64::MAX), BinaryOperator::Add, int(1));
+        assert!(matches!(overflow.eval(&record()), Err(DbError::Overflow(_))));
+        let text = Expr::binary(column("title"), BinaryOperator::Add, int(1));
+        assert!(matches!(text.eval(&record()), Err(DbError::TypeMismatch(_))));
+    }
+
+    #[test]
+    fn test_data_type_and_display() {
+        let columns = HashMap::from([
+            ("pages".to_string(), DataType::Int),
+            ("rating".to_string(), DataType::Float),
+            ("title".to_string(), DataType::String),
+        ]);
+        let sum = Expr::binary(column("pages"), BinaryOperator::Add, int(1));
+        let expr = Expr::binary(sum.clone(), BinaryOperator::Multiply, column("rating"));
+        assert!(matches!(sum.data_type(&columns), Ok(Some(DataType::Int))));
+        assert!(matches!(expr.data_type(&columns), Ok(Some(DataType::Float))));
+        assert_eq!(expr.to_string(), "(pages + 1) * rating");
+        assert_eq!(Expr::binary(int(1), BinaryOperator::Subtract, sum).to_string(), "1 - (pages + 1)");
+
+        let text = Expr::binary(column("title"), BinaryOperator::Multiply, int(2));
+        assert!(matches!(text.data_type(&columns), Err(DbError::TypeMismatch(_))));
+        assert!(matches!(column("year").data_type(&columns), Err(DbError::ColumnNotFound(_))));
+    }
+}
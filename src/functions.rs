@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use crate::commands::{evaluate_condition, Operator};
+use crate::domain::{DataType, Value};
+use crate::error::{DbError, DbResult};
+
+/// Checks the argument types of a call, where `None` stands for NULL, and returns the type of the result.
+type Signature = fn(&str, &[Option<DataType>]) -> DbResult<Option<DataType>>;
+
+/// A built-in scalar function callable from expressions, such as `UPPER(title)`.
+pub struct Builtin {
+    pub name: &'static str,
+    /// Whether any NULL argument makes the result NULL without calling `body`.
+    strict: bool,
+    /// Checked before execution, so that wrong arguments fail even on an empty table.
+    signature: Signature,
+    body: fn(Vec<Value>) -> DbResult<Value>,
+}
+
+impl Builtin {
+    pub fn return_type(&self, args: &[Option<DataType>]) -> DbResult<Option<DataType>> {
+        (self.signature)(self.name, args)
+    }
+
+    pub fn call(&self, args: Vec<Value>) -> DbResult<Value> {
+        if self.strict && args.contains(&Value::Null) {
+            return Ok(Value::Null);
+        }
+        (self.body)(args)
+    }
+}
+
+static BUILTINS: &[Builtin] = &[
+    Builtin { name: "UPPER", strict: true, signature: text_to_text, body: |args| map_text(args, |s| s.to_uppercase()) },
+    Builtin { name: "LOWER", strict: true, signature: text_to_text, body: |args| map_text(args, |s| s.to_lowercase()) },
+    Builtin { name: "TRIM", strict: true, signature: text_to_text, body: |args| map_text(args, |s| s.trim().into()) },
+    Builtin { name: "LENGTH", strict: true, signature: length_signature, body: length },
+    Builtin { name: "SUBSTR", strict: true, signature: substr_signature, body: substr },
+    Builtin { name: "REPLACE", strict: true, signature: replace_signature, body: replace },
+    Builtin { name: "ABS", strict: true, signature: numeric_to_numeric, body: abs },
+    Builtin { name: "ROUND", strict: true, signature: round_signature, body: round },
+    Builtin { name: "FLOOR", strict: true, signature: numeric_to_numeric, body: |args| map_float(args, f64::floor) },
+    Builtin { name: "CEIL", strict: true, signature: numeric_to_numeric, body: |args| map_float(args, f64::ceil) },
+    Builtin { name: "COALESCE", strict: false, signature: coalesce_signature, body: coalesce },
+    Builtin { name: "NULLIF", strict: false, signature: nullif_signature, body: nullif },
+];
+
+/// Looks up a built-in function; names are case-insensitive.
+pub fn builtin(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|function| function.name.eq_ignore_ascii_case(name))
+}
+
+/// A scalar function defined in Rust by the embedding application, such as a domain-specific
+/// predicate. It receives its arguments as they are, NULLs included.
+#[derive(Clone)]
+pub struct UserFunction {
+    pub name: String,
+    pub arity: usize,
+    pub return_type: DataType,
+    body: Arc<UserBody>,
+}
+
+type UserBody = dyn Fn(&[Value]) -> DbResult<Value> + Send + Sync;
+
+/// User-defined functions of a database, by upper-cased name.
+pub type UserFunctions = HashMap<String, Arc<UserFunction>>;
+
+impl UserFunction {
+    pub fn new<F>(name: &str, arity: usize, return_type: DataType, body: F) -> UserFunction
+    where
+        F: Fn(&[Value]) -> DbResult<Value> + Send + Sync + 'static,
+    {
+        UserFunction { name: name.to_string(), arity, return_type, body: Arc::new(body) }
+    }
+
+    pub fn check_arity(&self, args: usize) -> DbResult<()> {
+        if args != self.arity {
+            return Err(DbError::InvalidCommand(format!("{} takes {} arguments, got {}", self.name, self.arity, args)));
+        }
+        Ok(())
+    }
+
+    /// Calls the function and makes sure it returned a value of the declared type or NULL.
+    pub fn call(&self, args: &[Value]) -> DbResult<Value> {
+        self.check_arity(args.len())?;
+        match ((self.body)(args)?, &self.return_type) {
+            (Value::Int(n), DataType::Float) => Ok(Value::Float(n as f64)),
+            (value, return_type) if value.data_type().is_none_or(|t| t == *return_type) => Ok(value),
+            (value, return_type) => Err(DbError::TypeMismatch(format!(
+                "{} returned {} instead of a {}", self.name, value, return_type
+            ))),
+        }
+    }
+}
+
+impl fmt::Debug for UserFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UserFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .field("return_type", &self.return_type)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Functions are told apart by their signature, since closures cannot be compared.
+impl PartialEq for UserFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.arity == other.arity && self.return_type == other.return_type
+    }
+}
+
+/// Kinds of arguments a built-in function accepts.
+#[derive(Clone, Copy)]
+enum Param {
+    Text,
+    Int,
+    Numeric,
+}
+
+impl Param {
+    fn accepts(self, data_type: &DataType) -> bool {
+        match self {
+            Param::Text => *data_type == DataType::String,
+            Param::Int => *data_type == DataType::Int,
+            Param::Numeric => matches!(data_type, DataType::Int | DataType::Float),
+        }
+    }
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Param::Text => "a String",
+            Param::Int => "an Int",
+            Param::Numeric => "a number",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Checks that there are between `required` and `params.len()` arguments of the expected kinds.
+fn expect(name: &str, args: &[Option<DataType>], params: &[Param], required: usize) -> DbResult<()> {
+    if args.len() < required || args.len() > params.len() {
+        let count = if required == params.len() {
+            required.to_string()
+        } else {
+            format!("{} to {}", required, params.len())
+        };
+        return Err(DbError::InvalidCommand(format!("{} takes {} arguments, got {}", name, count, args.len())));
+    }
+    for (position, (arg, param)) in args.iter().zip(params).enumerate() {
+        if let Some(data_type) = arg
+            && !param.accepts(data_type) {
+            return Err(DbError::TypeMismatch(format!(
+                "Argument {} of {} must be {}, not {}", position + 1, name, param, data_type
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The type that values of all the given types can be shown as: equal types stay as they
+/// are, Int and Float widen to Float, and NULL fits anything.
+pub(crate) fn common_type(types: &[Option<DataType>]) -> DbResult<Option<DataType>> {
+    let mut common: Option<DataType> = None;
+    for data_type in types.iter().flatten() {
+        common = match (common, data_type) {
+            (None, data_type) => Some(data_type.clone()),
+            (Some(a), b) if a == *b => Some(a),
+            (Some(DataType::Int | DataType::Float), DataType::Int | DataType::Float) => Some(DataType::Float),
+            (Some(a), b) => return Err(DbError::TypeMismatch(format!("{} and {} have no common type", a, b))),
+        };
+    }
+    Ok(common)
+}
+
+fn text_to_text(name: &str, args: &[Option<DataType>]) -> DbResult<Option<DataType>> {
+    expect(name, args, &[Param::Text], 1)?;
+    Ok(Some(DataType::String))
+}
+
+fn length_signature(name: &str, args: &[Option<DataType>]) -> DbResult<Option<DataType>> {
+    expect(name, args, &[Param::Text], 1)?;
+    Ok(Some(DataType::Int))
+}
+
+fn substr_signature(name: &str, args: &[Option<DataType>]) -> DbResult<Option<DataType>> {
+    expect(name, args, &[Param::Text, Param::Int, Param::Int], 2)?;
+    Ok(Some(DataType::String))
+}
+
+fn replace_signature(name: &str, args: &[Option<DataType>]) -> DbResult<Option<DataType>> {
+    expect(name, args, &[Param::Text, Param::Text, Param::Text], 3)?;
+    Ok(Some(DataType::String))
+}
+
+fn numeric_to_numeric(name: &str, args: &[Option<DataType>]) -> DbResult<Option<DataType>> {
+    expect(name, args, &[Param::Numeric], 1)?;
+    Ok(args[0].clone())
+}
+
+fn round_signature(name: &str, args: &[Option<DataType>]) -> DbResult<Option<DataType>> {
+    expect(name, args, &[Param::Numeric, Param::Int], 1)?;
+    Ok(args[0].clone())
+}
+
+fn coalesce_signature(name: &str, args: &[Option<DataType>]) -> DbResult<Option<DataType>> {
+    if args.is_empty() {
+        return Err(DbError::InvalidCommand(format!("{} takes at least 1 argument", name)));
+    }
+    common_type(args)
+}
+
+fn nullif_signature(name: &str, args: &[Option<DataType>]) -> DbResult<Option<DataType>> {
+    if args.len() != 2 {
+        return Err(DbError::InvalidCommand(format!("{} takes 2 arguments, got {}", name, args.len())));
+    }
+    common_type(args)?;
+    Ok(args[0].clone())
+}
+
+fn bad_arguments(args: &[Value]) -> DbError {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    DbError::TypeMismatch(format!("Unexpected arguments ({})", args.join(", ")))
+}
+
+fn map_text(args: Vec<Value>, f: fn(&str) -> String) -> DbResult<Value> {
+    match args.as_slice() {
+        [Value::String(s)] => Ok(Value::String(f(s))),
+        _ => Err(bad_arguments(&args)),
+    }
+}
+
+fn map_float(args: Vec<Value>, f: fn(f64) -> f64) -> DbResult<Value> {
+    match args.as_slice() {
+        [Value::Int(n)] => Ok(Value::Int(*n)),
+        [Value::Float(x)] => Ok(Value::Float(f(*x))),
+        _ => Err(bad_arguments(&args)),
+    }
+}
+
+fn length(args: Vec<Value>) -> DbResult<Value> {
+    match args.as_slice() {
+        [Value::String(s)] => Ok(Value::Int(s.chars().count() as i64)),
+        _ => Err(bad_arguments(&args)),
+    }
+}
+
+/// `SUBSTR(s, start[, length])` with the first character at position 1. Positions outside
+/// the string are ignored, so `SUBSTR("abc", 0, 2)` is `"a"`.
+fn substr(args: Vec<Value>) -> DbResult<Value> {
+    let (s, start, length) = match args.as_slice() {
+        [Value::String(s), Value::Int(start)] => (s, *start, None),
+        [Value::String(s), Value::Int(start), Value::Int(length)] => (s, *start, Some(*length)),
+        _ => return Err(bad_arguments(&args)),
+    };
+    if length.is_some_and(|length| length < 0) {
+        return Err(DbError::InvalidCommand("SUBSTR length cannot be negative".into()));
+    }
+    let end = length.map_or(i64::MAX, |length| start.saturating_add(length));
+    let result = s.chars()
+        .zip(1i64..)
+        .filter(|(_, position)| *position >= start && *position < end)
+        .map(|(c, _)| c)
+        .collect();
+    Ok(Value::String(result))
+}
+
+fn replace(args: Vec<Value>) -> DbResult<Value> {
+    match args.as_slice() {
+        [Value::String(s), Value::String(from), Value::String(_)] if from.is_empty() => Ok(Value::String(s.clone())),
+        [Value::String(s), Value::String(from), Value::String(to)] => Ok(Value::String(s.replace(from, to))),
+        _ => Err(bad_arguments(&args)),
+    }
+}
+
+fn abs(args: Vec<Value>) -> DbResult<Value> {
+    match args.as_slice() {
+        [Value::Int(n)] => n.checked_abs().map(Value::Int)
+            .ok_or_else(|| DbError::Overflow(format!("ABS({})", n))),
+        [Value::Float(x)] => Ok(Value::Float(x.abs())),
+        _ => Err(bad_arguments(&args)),
+    }
+}
+
+/// `ROUND(x[, digits])` rounds half away from zero; negative `digits` round to tens, hundreds and so on.
+fn round(args: Vec<Value>) -> DbResult<Value> {
+    let (value, digits) = match args.as_slice() {
+        [value] => (value, 0),
+        [value, Value::Int(digits)] => (value, *digits),
+        _ => return Err(bad_arguments(&args)),
+    };
+    match value {
+        Value::Float(x) => {
+            let scale = 10f64.powi(digits.clamp(-308, 308) as i32);
+            Ok(Value::Float((x * scale).round() / scale))
+        }
+        Value::Int(n) if digits >= 0 => Ok(Value::Int(*n)),
+        Value::Int(n) => {
+            let Some(step) = u32::try_from(-digits).ok().and_then(|exponent| 10i64.checked_pow(exponent)) else {
+                return Ok(Value::Int(0));
+            };
+            let rest = n % step;
+            let rounded = if rest.abs() >= step / 2 {
+                (n - rest).checked_add(step * n.signum())
+            } else {
+                Some(n - rest)
+            };
+            rounded.map(Value::Int)
+                .ok_or_else(|| DbError::Overflow(format!("ROUND({}, {})", n, digits)))
+        }
+        _ => Err(bad_arguments(&args)),
+    }
+}
+
+fn coalesce(args: Vec<Value>) -> DbResult<Value> {
+    Ok(args.into_iter().find(|value| *value != Value::Null).unwrap_or(Value::Null))
+}
+
+fn nullif(args: Vec<Value>) -> DbResult<Value> {
+    match <[Value; 2]>::try_from(args) {
+        Ok([value, other]) if evaluate_condition(&value, &other, &Operator::Equal) == Some(true) => Ok(Value::Null),
+        Ok([value, _]) => Ok(value),
+        Err(args) => Err(bad_arguments(&args)),
+    }
+}
+
+/// Converts a value to another type, as in `CAST(pages AS String)`. NULL stays NULL, while
+/// a string that does not spell a value of the target type is an error.
+pub fn cast(value: Value, data_type: &DataType) -> DbResult<Value> {
+    let failed = |value: &Value| DbError::TypeMismatch(format!("Cannot cast {} to {}", value, data_type));
+    Ok(match (value, data_type) {
+        (Value::Null, _) => Value::Null,
+        (Value::Int(n), DataType::Int) => Value::Int(n),
+        (Value::Int(n), DataType::Float) => Value::Float(n as f64),
+        (Value::Int(n), DataType::Bool) => Value::Bool(n != 0),
+        (Value::Float(x), DataType::Int) if x.is_finite() && x.trunc().abs() < i64::MAX as f64 => Value::Int(x as i64),
+        (Value::Float(x), DataType::Float) => Value::Float(x),
+        (Value::Float(x), DataType::Bool) => Value::Bool(x != 0.0),
+        (Value::Bool(b), DataType::Int) => Value::Int(b as i64),
+        (Value::Bool(b), DataType::Float) => Value::Float(b as i64 as f64),
+        (Value::Bool(b), DataType::Bool) => Value::Bool(b),
+        (Value::String(s), DataType::Int) => s.trim().parse().map(Value::Int).map_err(|_| failed(&Value::String(s)))?,
+        (Value::String(s), DataType::Float) => {
+            s.trim().parse().map(Value::Float).map_err(|_| failed(&Value::String(s)))?
+        }
+        (Value::String(s), DataType::Bool) => match s.trim() {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => return Err(failed(&Value::String(s))),
+        },
+        (value, DataType::String) => Value::String(value.to_string()),
+        (value, _) => return Err(failed(&value)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, args: Vec<Value>) -> Value {
+        match builtin(name).map(|function| function.call(args)) {
+            Some(Ok(value)) => value,
+            other => panic!("Calling {} failed: {:?}", name, other.map(|result| result.err())),
+        }
+    }
+
+    fn text(s: &str) -> Value {
+        Value::String(s.into())
+    }
+
+    #[test]
+    fn test_builtin_functions() {
+        let cases = [
+            ("upper", vec![text("Dune")], text("DUNE")),
+            ("LOWER", vec![text("Dune")], text("dune")),
+            ("TRIM", vec![text("  Dune ")], text("Dune")),
+            ("LENGTH", vec![text("Łódź")], Value::Int(4)),
+            ("SUBSTR", vec![text("Homotopy"), Value::Int(3), Value::Int(4)], text("moto")),
+            ("SUBSTR", vec![text("abc"), Value::Int(0), Value::Int(2)], text("a")),
+            ("SUBSTR", vec![text("abc"), Value::Int(2)], text("bc")),
+            ("REPLACE", vec![text("a-b-c"), text("-"), text("+")], text("a+b+c")),
+            ("ABS", vec![Value::Int(-3)], Value::Int(3)),
+            ("ROUND", vec![Value::Float(2.345), Value::Int(1)], Value::Float(2.3)),
+            ("ROUND", vec![Value::Float(-2.5)], Value::Float(-3.0)),
+            ("ROUND", vec![Value::Int(1250), Value::Int(-2)], Value::Int(1300)),
+            ("ROUND", vec![Value::Int(-1249), Value::Int(-2)], Value::Int(-1200)),
+            ("FLOOR", vec![Value::Float(-1.5)], Value::Float(-2.0)),
+            ("CEIL", vec![Value::Float(1.2)], Value::Float(2.0)),
+            ("UPPER", vec![Value::Null], Value::Null),
+            ("COALESCE", vec![Value::Null, Value::Int(2), Value::Int(3)], Value::Int(2)),
+            ("NULLIF", vec![Value::Int(2), Value::Float(2.0)], Value::Null),
+            ("NULLIF", vec![Value::Int(2), Value::Int(3)], Value::Int(2)),
+        ];
+        for (name, args, expected) in cases {
+            assert_eq!(call(name, args.clone()), expected, "{}({:?})", name, args);
+        }
+        assert!(builtin("FROBNICATE").is_none());
+        let abs_of_min = builtin("ABS").map(|function| function.call(vec![Value::Int(i64::MIN)]));
+        assert!(matches!(abs_of_min, Some(Err(DbError::Overflow(_)))));
+    }
+
+    #[test]
+    fn test_builtin_signatures() {
+        let signature = |name: &str, args: &[Option<DataType>]| match builtin(name) {
+            Some(function) => function.return_type(args),
+            None => panic!("{} is not a builtin", name),
+        };
+        assert!(matches!(signature("LENGTH", &[Some(DataType::String)]), Ok(Some(DataType::Int))));
+        assert!(matches!(signature("ABS", &[Some(DataType::Float)]), Ok(Some(DataType::Float))));
+        assert!(matches!(
+            signature("COALESCE", &[None, Some(DataType::Int), Some(DataType::Float)]),
+            Ok(Some(DataType::Float))
+        ));
+        assert!(matches!(signature("UPPER", &[Some(DataType::Int)]), Err(DbError::TypeMismatch(_))));
+        assert!(matches!(signature("SUBSTR", &[Some(DataType::String)]), Err(DbError::InvalidCommand(_))));
+        assert!(matches!(
+            signature("COALESCE", &[Some(DataType::String), Some(DataType::Int)]),
+            Err(DbError::TypeMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_cast() {
+        let cases = [
+            (text(" 42 "), DataType::Int, Value::Int(42)),
+            (Value::Float(2.9), DataType::Int, Value::Int(2)),
+            (Value::Int(3), DataType::Float, Value::Float(3.0)),
+            (Value::Float(2.5), DataType::String, text("2.5")),
+            (text("true"), DataType::Bool, Value::Bool(true)),
+            (Value::Null, DataType::Int, Value::Null),
+        ];
+        for (value, data_type, expected) in cases {
+            match cast(value.clone(), &data_type) {
+                Ok(result) => assert_eq!(result, expected),
+                Err(e) => panic!("Casting {} to {} failed: {:?}", value, data_type, e),
+            }
+        }
+        assert!(matches!(cast(text("x"), &DataType::Int), Err(DbError::TypeMismatch(_))));
+        assert!(matches!(cast(Value::Float(f64::NAN), &DataType::Int), Err(DbError::TypeMismatch(_))));
+    }
+}
@@ -2,4 +2,9 @@ pub mod error;
 pub mod domain;
 pub mod database;
 pub mod commands;
-pub mod queries;
\ No newline at end of file
+pub mod expression;
+pub mod functions;
+pub mod queries;
+pub mod results;
+pub mod storage;
+pub mod wal;
\ No newline at end of file
@@ -1,24 +1,58 @@
-use clap::Parser;
-use std::{fs, io};
+use clap::{Parser, ValueEnum};
+use std::{fmt, fs, io};
+use std::path::PathBuf;
 use std::io::Write;
 use rust_database_project::{
     database::{AnyDatabase, Database},
     queries::{parse, Query},
+    results::{QueryResult, ResultSet},
     error::DbError,
 };
 
 #[derive(Parser)]
 struct Args {
-    #[arg(short, long, default_value = "string")]
-    key_type: String,
+    /// Key type of every table; `auto` keys each table by its primary-key column type.
+    #[arg(short, long, value_enum, default_value_t = KeyType::Auto)]
+    key_type: KeyType,
+    /// Directory the tables are loaded from at startup and saved to on exit.
+    #[arg(short, long)]
+    data_dir: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum KeyType {
+    Int,
+    String,
+    Auto,
+}
+
+impl fmt::Display for KeyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyType::Int => write!(f, "int"),
+            KeyType::String => write!(f, "string"),
+            KeyType::Auto => write!(f, "auto"),
+        }
+    }
 }
 
 fn main() {
     let args = Args::parse();
 
-    let mut db = match args.key_type.as_str() {
-        "int" => AnyDatabase::IntDatabase(Database::new()),
-        _ => AnyDatabase::StringDatabase(Database::new()),
+    let opened = match (args.key_type, &args.data_dir) {
+        (KeyType::Int, Some(dir)) => Database::open(dir).map(AnyDatabase::IntDatabase),
+        (KeyType::Int, None) => Ok(AnyDatabase::IntDatabase(Database::new())),
+        (KeyType::String, Some(dir)) => Database::open(dir).map(AnyDatabase::StringDatabase),
+        (KeyType::String, None) => Ok(AnyDatabase::StringDatabase(Database::new())),
+        (KeyType::Auto, Some(dir)) => Database::open(dir).map(AnyDatabase::DynamicDatabase),
+        (KeyType::Auto, None) => Ok(AnyDatabase::DynamicDatabase(Database::new())),
+    };
+    let mut db = match opened {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return;
+        }
     };
 
     println!("Database is ready (Key type: {}).", args.key_type);
@@ -32,7 +66,10 @@ fn main() {
         io::stdout().flush().ok();
         buffer.clear();
 
-        if stdin.read_line(&mut buffer).is_err() { break; }
+        match stdin.read_line(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
         let input = buffer.trim();
         if input.is_empty() { continue; }
         if input.eq_ignore_ascii_case("exit") || input.eq_ignore_ascii_case("quit") {
@@ -43,6 +80,17 @@ fn main() {
             eprintln!("Error: {}", e);
         }
     }
+
+    if db.in_transaction() && db.execute(Query::Rollback).is_ok() {
+        println!("Open transaction rolled back.");
+    }
+
+    if let Some(dir) = &args.data_dir {
+        match db.checkpoint() {
+            Ok(()) => println!("Saved database to: {}", dir.display()),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
 }
 
 fn process_command(db: &mut AnyDatabase, input: &str, history: &mut Vec<String>) -> Result<(), DbError> {
@@ -53,29 +101,61 @@ fn process_command(db: &mut AnyDatabase, input: &str, history: &mut Vec<String>)
             fs::write(&path, history.join("\n"))?;
             println!("Saved history to: {}", path);
         }
-        Query::ReadFrom(path) => {
+        Query::ReadFrom { path, atomic } => {
             let content = fs::read_to_string(&path)?;
+            let history_len = history.len();
+            if atomic {
+                db.execute(Query::Begin)?;
+            }
             for line in content.lines() {
                 if !line.trim().is_empty() {
                     println!("FILE> {}", line);
-                    process_command(db, line, history)?;
+                    if let Err(e) = process_command(db, line, history) {
+                        if atomic {
+                            db.execute(Query::Rollback)?;
+                            history.truncate(history_len);
+                            println!("Transaction rolled back.");
+                        }
+                        return Err(e);
+                    }
                 }
             }
+            if atomic {
+                db.execute(Query::Commit)?;
+            }
         }
         _ => {
-            if let Some(result) = db.execute(query)? {
-                println!("{}", result);
-            }
+            let result = db.execute(query)?;
+            println!("{}", format_result(&result));
             history.push(input.to_string());
         }
     }
     Ok(())
 }
 
+fn format_result(result: &QueryResult) -> String {
+    match result {
+        QueryResult::Rows(result_set) => format_result_set(result_set),
+        QueryResult::RowsAffected(1) => "1 row affected".to_string(),
+        QueryResult::RowsAffected(count) => format!("{} rows affected", count),
+        QueryResult::Message(message) => message.clone(),
+    }
+}
+
+fn format_result_set(result_set: &ResultSet) -> String {
+    let mut lines = vec![result_set.columns.join(", ")];
+    for row in &result_set.rows {
+        let values: Vec<String> = row.iter().map(|value| value.to_string()).collect();
+        lines.push(values.join(", "));
+    }
+    lines.join("\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::path::Path;
+    use rust_database_project::domain::{DataType, Value};
 
     fn cleanup(path: &str) {
         let _ = fs::remove_file(path);
@@ -103,12 +183,26 @@ mod tests {
                 let expected = "CREATE t KEY k FIELDS k:String\nINSERT k=\"x\" INTO t";
                 assert_eq!(content.trim(), expected);
             }
-            Err(e) => assert!(false, "Cannot read the file: {}", e),
+            Err(e) => panic!("Cannot read the file: {}", e),
         }
 
         cleanup(path_str);
     }
 
+    #[test]
+    fn test_format_result_set() {
+        let result = QueryResult::Rows(ResultSet {
+            columns: vec!["id".into(), "name".into()],
+            types: vec![DataType::Int, DataType::String],
+            rows: vec![
+                vec![Value::Int(1), Value::String("a".into())],
+                vec![Value::Int(2), Value::Null],
+            ],
+        });
+        assert_eq!(format_result(&result), "id, name\n1, a\n2, NULL");
+        assert_eq!(format_result(&QueryResult::RowsAffected(3)), "3 rows affected");
+    }
+
     #[test]
     fn test_process_read_from() {
         let path_str = "test_script_read.txt";
@@ -116,7 +210,7 @@ mod tests {
 
         let script_content = "CREATE users KEY id FIELDS id:String, age:Int\nINSERT id=\"u1\", age=20 INTO users";
         if let Err(e) = fs::write(path_str, script_content) {
-            assert!(false, "Error while setting up a test (file write): {}", e);
+            panic!("Error while setting up a test (file write): {}", e);
         }
 
         let mut db = AnyDatabase::StringDatabase(Database::new());
@@ -133,4 +227,36 @@ mod tests {
 
         cleanup(path_str);
     }
+
+    #[test]
+    fn test_process_read_from_atomic_rolls_back() {
+        let path_str = "test_script_read_atomic.txt";
+        cleanup(path_str);
+
+        let script_content = "INSERT id=\"u2\", age=30 INTO users\nINSERT id=\"u1\", age=40 INTO users";
+        if let Err(e) = fs::write(path_str, script_content) {
+            panic!("Error while setting up a test (file write): {}", e);
+        }
+
+        let mut db = AnyDatabase::StringDatabase(Database::new());
+        let mut history = Vec::new();
+        for line in ["CREATE users KEY id FIELDS id:String, age:Int", "INSERT id=\"u1\", age=20 INTO users"] {
+            if let Err(e) = process_command(&mut db, line, &mut history) {
+                panic!("Setup command failed: {:?}", e);
+            }
+        }
+
+        let cmd = format!("READ_FROM {} ATOMIC", path_str);
+        let res = process_command(&mut db, &cmd, &mut history);
+        assert!(matches!(res, Err(DbError::DuplicateKey)), "Expected a duplicate key: {:?}", res);
+        assert!(db.execute(Query::Commit).is_err(), "Transaction should have been closed");
+        assert_eq!(history.len(), 2, "Rolled back lines should not be kept in history");
+
+        match parse("SELECT id FROM users").and_then(|query| db.execute(query)) {
+            Ok(QueryResult::Rows(result_set)) => assert_eq!(result_set.rows, vec![vec![Value::String("u1".into())]]),
+            other => panic!("Expected rows: {:?}", other),
+        }
+
+        cleanup(path_str);
+    }
 }
\ No newline at end of file
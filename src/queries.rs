@@ -1,5 +1,10 @@
-use crate::domain::{Value, DataType};
-use crate::commands::{Operator, Condition};
+use crate::domain::{ColumnDef, Value, DataType};
+use crate::commands::{
+    Operator, Condition, SortOrder, SelectItem, SelectField, Aggregate, AggregateFunction, Join, JoinKind, AlterAction,
+    OnConflict,
+};
+use crate::expression::{BinaryOperator, Expr};
+use crate::functions::UserFunctions;
 use pest::Parser;
 use pest_derive::Parser;
 use crate::error::{DbError, DbResult};
@@ -12,24 +17,106 @@ pub struct QueryParser;
 pub enum Query {
     Select {
         table: String,
-        fields: Vec<String>,
+        joins: Vec<Join>,
+        fields: Vec<SelectField>,
         condition: Option<Condition>,
+        group_by: Vec<String>,
+        having: Option<Condition>,
+        order_by: Vec<(SelectItem, SortOrder)>,
+        limit: Option<usize>,
+        offset: Option<usize>,
     },
     Create {
         table: String,
-        pk: String,
-        columns: Vec<(String, DataType)>,
+        /// Primary-key columns, more than one for a composite key.
+        pk: Vec<String>,
+        columns: Vec<ColumnDef>,
     },
+    /// Inserts one record per row, all or none of them.
     Insert {
+        table: String,
+        rows: Vec<Vec<(String, Value)>>,
+        on_conflict: OnConflict,
+    },
+    /// Inserts every row returned by a SELECT, matching its output columns by name.
+    InsertSelect {
+        table: String,
+        select: Box<Query>,
+        on_conflict: OnConflict,
+    },
+    Update {
         table: String,
         values: Vec<(String, Value)>,
+        condition: Option<Condition>,
     },
     Delete {
         table: String,
-        key_value: Value,
+        /// One value per primary-key column.
+        key: Vec<Value>,
+    },
+    /// Deletes every record matching the condition, or all records without one.
+    DeleteWhere {
+        table: String,
+        condition: Option<Condition>,
+    },
+    DropTable {
+        table: String,
+        if_exists: bool,
+    },
+    Truncate {
+        table: String,
+    },
+    AlterTable {
+        table: String,
+        action: AlterAction,
+    },
+    ShowTables,
+    Describe {
+        table: String,
+    },
+    CreateIndex {
+        name: String,
+        table: String,
+        column: String,
+    },
+    DropIndex {
+        name: String,
     },
     SaveAs(String),
-    ReadFrom(String),
+    ReadFrom {
+        path: String,
+        atomic: bool,
+    },
+    Begin,
+    Commit,
+    Rollback,
+}
+
+impl Query {
+    /// Binds calls of user-defined functions anywhere in the query, see [`Expr::bind`].
+    pub fn bind(&mut self, functions: &UserFunctions) {
+        if functions.is_empty() {
+            return;
+        }
+        match self {
+            Query::Select { fields, condition, having, order_by, .. } => {
+                let items = fields.iter_mut().map(|field| &mut field.item)
+                    .chain(order_by.iter_mut().map(|(item, _)| item));
+                for item in items {
+                    if let SelectItem::Expression(expr) = item {
+                        expr.bind(functions);
+                    }
+                }
+                for condition in condition.iter_mut().chain(having) {
+                    condition.bind(functions);
+                }
+            }
+            Query::InsertSelect { select, .. } => select.bind(functions),
+            Query::Update { condition: Some(condition), .. }
+            | Query::DeleteWhere { condition: Some(condition), .. } => condition.bind(functions),
+            _ => {}
+        }
+    }
 }
 
 pub fn parse(input: &str) -> DbResult<Query> {
@@ -40,14 +127,43 @@ pub fn parse(input: &str) -> DbResult<Query> {
     match pair.as_rule() {
         Rule::select_cmd => parse_select_command(pair),
         Rule::create_cmd => parse_create_command(pair),
+        Rule::create_index_cmd => parse_create_index_command(pair),
+        Rule::show_tables_cmd => Ok(Query::ShowTables),
+        Rule::describe_cmd => pair.into_inner().next()
+            .map(|p| Query::Describe { table: p.as_str().to_string() })
+            .ok_or(DbError::SyntaxError("No table in DESCRIBE".into())),
+        Rule::drop_table_cmd => {
+            let mut inner = pair.into_inner();
+            let mut table = inner.next().ok_or(DbError::SyntaxError("No table in DROP TABLE".into()))?;
+            let if_exists = table.as_rule() == Rule::if_exists;
+            if if_exists {
+                table = inner.next().ok_or(DbError::SyntaxError("No table in DROP TABLE".into()))?;
+            }
+            Ok(Query::DropTable {table: table.as_str().to_string(), if_exists})
+        }
+        Rule::truncate_cmd => pair.into_inner().find(|p| p.as_rule() == Rule::ident)
+            .map(|p| Query::Truncate { table: p.as_str().to_string() })
+            .ok_or(DbError::SyntaxError("No table in TRUNCATE".into())),
+        Rule::alter_cmd => parse_alter_command(pair),
+        Rule::drop_index_cmd => pair.into_inner().next()
+            .map(|p| Query::DropIndex { name: p.as_str().to_string() })
+            .ok_or(DbError::SyntaxError("No index name in DROP INDEX".into())),
         Rule::delete_cmd => parse_delete_command(pair),
+        Rule::delete_where_cmd => parse_delete_where_command(pair),
         Rule::insert_cmd => parse_insert_command(pair),
+        Rule::insert_select_cmd => parse_insert_select_command(pair),
+        Rule::update_cmd => parse_update_command(pair),
         Rule::save_cmd => pair.into_inner().next()
             .map(|p| Query::SaveAs(p.as_str().to_string()))
             .ok_or(DbError::InvalidPath("No path".into())),
-        Rule::read_cmd => pair.into_inner().next()
-            .map(|p| Query::ReadFrom(p.as_str().to_string()))
-            .ok_or(DbError::InvalidPath("No path".into())),
+        Rule::read_cmd => {
+            let mut inner = pair.into_inner();
+            let path = inner.next().ok_or(DbError::InvalidPath("No path".into()))?;
+            Ok(Query::ReadFrom { path: path.as_str().to_string(), atomic: inner.next().is_some() })
+        }
+        Rule::begin_cmd => Ok(Query::Begin),
+        Rule::commit_cmd => Ok(Query::Commit),
+        Rule::rollback_cmd => Ok(Query::Rollback),
         _ => Err(DbError::SyntaxError("Invalid query format".into())),
     }
 }
@@ -55,25 +171,258 @@ pub fn parse(input: &str) -> DbResult<Query> {
 fn parse_select_command(pair: pest::iterators::Pair<Rule>) -> DbResult<Query> {
     let inner = pair.into_inner();
     let mut fields = Vec::new();
+    let mut table = None;
+    let mut joins = Vec::new();
     let mut cond = None;
+    let mut group_by = Vec::new();
+    let mut having = None;
+    let mut order_by = Vec::new();
+    let mut limit = None;
+    let mut offset = None;
     for p in inner {
         match p.as_rule() {
-            Rule::ident => fields.push(p.as_str().to_string()),
+            Rule::select_item => fields.push(parse_select_item(p)?),
+            Rule::ident => table = Some(p.as_str().to_string()),
+            Rule::join_clause => joins.push(parse_join(p)?),
             Rule::where_clause => cond = Some(parse_where(p)?),
+            Rule::group_clause => group_by = p.into_inner().map(|c| c.as_str().to_string()).collect(),
+            Rule::having_clause => {
+                let condition = p.into_inner().next()
+                    .ok_or(DbError::SyntaxError("No condition in HAVING".into()))?;
+                having = Some(parse_condition(condition)?);
+            }
+            Rule::order_clause => {
+                for item in p.into_inner() {
+                    order_by.push(parse_order_item(item)?);
+                }
+            }
+            Rule::limit_clause => limit = Some(parse_count(p, "LIMIT")?),
+            Rule::offset_clause => offset = Some(parse_count(p, "OFFSET")?),
             _ => {}
         }
     }
-    let table = fields.pop().ok_or(DbError::SyntaxError("No table in SELECT".into()))?;
-    Ok(Query::Select { table, fields, condition: cond })
+    let table = table.ok_or(DbError::SyntaxError("No table in SELECT".into()))?;
+    Ok(Query::Select { table, joins, fields, condition: cond, group_by, having, order_by, limit, offset })
+}
+
+fn parse_join(pair: pest::iterators::Pair<Rule>) -> DbResult<Join> {
+    let mut inner = pair.into_inner().peekable();
+    let kind = match inner.peek().map(|p| p.as_rule()) {
+        Some(Rule::join_kind) => match inner.next().map(|p| p.as_str().starts_with("LEFT")) {
+            Some(true) => JoinKind::Left,
+            _ => JoinKind::Inner,
+        },
+        _ => JoinKind::Inner,
+    };
+    let table = inner.next().map(|p| p.as_str().to_string())
+        .ok_or(DbError::SyntaxError("No table in JOIN".into()))?;
+    let first = inner.next().map(|p| p.as_str().to_string())
+        .ok_or(DbError::SyntaxError("No column in JOIN".into()))?;
+    let second = inner.next().map(|p| p.as_str().to_string())
+        .ok_or(DbError::SyntaxError("No column in JOIN".into()))?;
+    // The joined table's column may be written on either side of the ON equality.
+    let (left_column, right_column) = if first.starts_with(&format!("{}.", table)) {
+        (second, first)
+    } else {
+        (first, second)
+    };
+    Ok(Join {kind, table, left_column, right_column})
+}
+
+fn parse_select_item(pair: pest::iterators::Pair<Rule>) -> DbResult<SelectField> {
+    let mut inner = pair.into_inner();
+    let item = inner.next()
+        .ok_or(DbError::SyntaxError("Empty select item".into()))?;
+    let item = match item.as_rule() {
+        Rule::all_columns => SelectItem::Wildcard,
+        _ => expression_item(parse_expr(item)?),
+    };
+    let alias = inner.next()
+        .and_then(|a| a.into_inner().next())
+        .map(|a| a.as_str().to_string());
+    Ok(SelectField {item, alias})
+}
+
+/// Plain columns and aggregates keep their own select item, so they stay visible to
+/// GROUP BY checks and ORDER BY alias resolution.
+fn expression_item(expr: Expr) -> SelectItem {
+    match expr {
+        Expr::Column(column) => SelectItem::Column(column),
+        Expr::Aggregate(aggregate) => SelectItem::Aggregate(aggregate),
+        expr => SelectItem::Expression(expr),
+    }
+}
+
+fn parse_expr(pair: pest::iterators::Pair<Rule>) -> DbResult<Expr> {
+    match pair.as_rule() {
+        Rule::expr | Rule::sum | Rule::product => {
+            let mut inner = pair.into_inner();
+            let first = inner.next().ok_or(DbError::SyntaxError("Empty expression".into()))?;
+            let mut result = parse_expr(first)?;
+            while let Some(op) = inner.next() {
+                let operator = match op.as_str() {
+                    "+" => BinaryOperator::Add,
+                    "-" => BinaryOperator::Subtract,
+                    "*" => BinaryOperator::Multiply,
+                    "/" => BinaryOperator::Divide,
+                    "%" => BinaryOperator::Remainder,
+                    "||" => BinaryOperator::Concat,
+                    _ => return Err(DbError::SyntaxError("Invalid arithmetic operator".into())),
+                };
+                let right = inner.next().ok_or(DbError::SyntaxError(format!("No operand after {}", operator)))?;
+                result = Expr::binary(result, operator, parse_expr(right)?);
+            }
+            Ok(result)
+        }
+        Rule::factor => {
+            let mut inner = pair.into_inner();
+            let first = inner.next().ok_or(DbError::SyntaxError("Empty expression".into()))?;
+            if first.as_rule() != Rule::neg_op {
+                return parse_expr(first);
+            }
+            let operand = inner.next().ok_or(DbError::SyntaxError("No operand after -".into()))?;
+            // Negative numbers are literals, so that they can still be used for index lookups.
+            Ok(match parse_expr(operand)? {
+                Expr::Literal(Value::Int(n)) => Expr::Literal(Value::Int(-n)),
+                Expr::Literal(Value::Float(f)) => Expr::Literal(Value::Float(-f)),
+                operand => Expr::Negate(Box::new(operand)),
+            })
+        }
+        Rule::aggregate => Ok(Expr::Aggregate(parse_aggregate(pair)?)),
+        Rule::function_call => {
+            let mut inner = pair.into_inner();
+            let name = inner.next().map(|p| p.as_str().to_string())
+                .ok_or(DbError::SyntaxError("No function name".into()))?;
+            let args = inner.map(parse_expr).collect::<DbResult<Vec<Expr>>>()?;
+            Ok(Expr::Function {name, args})
+        }
+        Rule::case_expr => {
+            let mut branches = Vec::new();
+            let mut otherwise = None;
+            for part in pair.into_inner() {
+                match part.as_rule() {
+                    Rule::when_branch => {
+                        let mut inner = part.into_inner();
+                        let condition = inner.next().ok_or(DbError::SyntaxError("No condition in WHEN".into()))?;
+                        let result = inner.next().ok_or(DbError::SyntaxError("No result in THEN".into()))?;
+                        branches.push((parse_condition(condition)?, parse_expr(result)?));
+                    }
+                    Rule::else_branch => {
+                        let result = part.into_inner().next().ok_or(DbError::SyntaxError("No result in ELSE".into()))?;
+                        otherwise = Some(Box::new(parse_expr(result)?));
+                    }
+                    _ => {}
+                }
+            }
+            Ok(Expr::Case {branches, otherwise})
+        }
+        Rule::cast_expr => {
+            let mut inner = pair.into_inner();
+            let expr = parse_expr(inner.next().ok_or(DbError::SyntaxError("No expression in CAST".into()))?)?;
+            let data_type = parse_type(inner.next().ok_or(DbError::SyntaxError("No type in CAST".into()))?)?;
+            Ok(Expr::Cast {expr: Box::new(expr), data_type})
+        }
+        Rule::value => Ok(Expr::Literal(parse_value(pair)?)),
+        Rule::column_ref => Ok(Expr::Column(pair.as_str().to_string())),
+        _ => Err(DbError::SyntaxError("Invalid expression".into())),
+    }
+}
+
+fn parse_aggregate(pair: pest::iterators::Pair<Rule>) -> DbResult<Aggregate> {
+    let mut inner = pair.into_inner();
+    let function = match inner.next().map(|p| p.as_str()) {
+        Some("COUNT") => AggregateFunction::Count,
+        Some("SUM") => AggregateFunction::Sum,
+        Some("AVG") => AggregateFunction::Avg,
+        Some("MIN") => AggregateFunction::Min,
+        Some("MAX") => AggregateFunction::Max,
+        _ => return Err(DbError::SyntaxError("Unknown aggregate function".into())),
+    };
+    let argument = inner.next().ok_or(DbError::SyntaxError("No argument in aggregate".into()))?;
+    let column = match argument.as_rule() {
+        Rule::all_columns if function == AggregateFunction::Count => None,
+        Rule::all_columns => return Err(DbError::SyntaxError("Only COUNT accepts *".into())),
+        _ => Some(argument.as_str().to_string()),
+    };
+    Ok(Aggregate {function, column})
+}
+
+fn parse_order_item(pair: pest::iterators::Pair<Rule>) -> DbResult<(SelectItem, SortOrder)> {
+    let mut inner = pair.into_inner();
+    let column = inner.next()
+        .ok_or(DbError::SyntaxError("No column in ORDER BY".into()))?;
+    let column = expression_item(parse_expr(column)?);
+    let order = match inner.next().map(|p| p.as_str()) {
+        Some("DESC") => SortOrder::Descending,
+        _ => SortOrder::Ascending,
+    };
+    Ok((column, order))
+}
+
+fn parse_count(pair: pest::iterators::Pair<Rule>, clause: &str) -> DbResult<usize> {
+    pair.into_inner().next()
+        .ok_or(DbError::SyntaxError(format!("No number in {}", clause)))?
+        .as_str().parse()
+        .map_err(|_| DbError::SyntaxError(format!("Bad number in {}", clause)))
 }
 
 fn parse_where(pair: pest::iterators::Pair<Rule>) -> DbResult<Condition> {
+    let condition = pair.into_inner().next()
+        .ok_or(DbError::SyntaxError("No condition in WHERE".into()))?;
+    let condition = parse_condition(condition)?;
+    if !condition.aggregates().is_empty() {
+        return Err(DbError::SyntaxError("Aggregates are not allowed in WHERE".into()));
+    }
+    Ok(condition)
+}
+
+fn parse_condition(pair: pest::iterators::Pair<Rule>) -> DbResult<Condition> {
+    match pair.as_rule() {
+        Rule::condition | Rule::and_condition => {
+            let is_or = pair.as_rule() == Rule::condition;
+            let mut operands = pair.into_inner()
+                .filter(|p| p.as_rule() != Rule::or_op && p.as_rule() != Rule::and_op);
+            let first = operands.next().ok_or(DbError::SyntaxError("Empty condition".into()))?;
+            let mut result = parse_condition(first)?;
+            for operand in operands {
+                let right = Box::new(parse_condition(operand)?);
+                result = if is_or {
+                    Condition::Or(Box::new(result), right)
+                } else {
+                    Condition::And(Box::new(result), right)
+                };
+            }
+            Ok(result)
+        }
+        Rule::not_condition => {
+            let mut inner = pair.into_inner();
+            let first = inner.next().ok_or(DbError::SyntaxError("Empty condition".into()))?;
+            if first.as_rule() == Rule::not_op {
+                let negated = inner.next().ok_or(DbError::SyntaxError("No condition after NOT".into()))?;
+                Ok(Condition::Not(Box::new(parse_condition(negated)?)))
+            } else {
+                parse_condition(first)
+            }
+        }
+        Rule::comparison => parse_comparison(pair),
+        Rule::like_check | Rule::in_check | Rule::between_check => parse_predicate(pair),
+        Rule::null_check => {
+            let mut inner = pair.into_inner();
+            let column = inner.next().map(|p| p.as_str().to_string())
+                .ok_or(DbError::SyntaxError("No column in IS NULL".into()))?;
+            let negated = inner.next().is_some_and(|p| p.as_rule() == Rule::not_op);
+            Ok(Condition::IsNull {column, negated})
+        }
+        _ => Err(DbError::SyntaxError("Invalid condition".into())),
+    }
+}
+
+fn parse_comparison(pair: pest::iterators::Pair<Rule>) -> DbResult<Condition> {
     let mut inner = pair.into_inner();
-    let column = inner.next().map(|p| p.as_str().to_string())
-        .ok_or(DbError::SyntaxError("No column in WHERE".into()))?;
+    let left = parse_expr(inner.next().ok_or(DbError::SyntaxError("No column in WHERE".into()))?)?;
     let op = inner.next().map(|p| p.as_str())
         .ok_or(DbError::SyntaxError("No operator".into()))?;
-    let value = parse_value(inner.next().ok_or(DbError::SyntaxError("No value in WHERE".into()))?)?;
+    let right = parse_expr(inner.next().ok_or(DbError::SyntaxError("No value in WHERE".into()))?)?;
     let operator = match op {
         "=" => Operator::Equal,
         "!=" => Operator::NotEqual,
@@ -83,7 +432,40 @@ fn parse_where(pair: pest::iterators::Pair<Rule>) -> DbResult<Condition> {
         ">" => Operator::GreaterThan,
         _ => return Err(DbError::SyntaxError("Invalid operator".into())),
     };
-    Ok(Condition {column, operator, value})
+    // A column compared with a constant stays a plain comparison, which indexes can answer.
+    Ok(match (left, right) {
+        (Expr::Column(column), Expr::Literal(value)) => Condition::Comparison {column, operator, value},
+        (Expr::Aggregate(aggregate), Expr::Literal(value)) => {
+            Condition::AggregateComparison {aggregate, operator, value}
+        }
+        (left, right) => Condition::Compare {left: Box::new(left), operator, right: Box::new(right)},
+    })
+}
+
+/// Parses `LIKE`, `IN` and `BETWEEN`, each of which can be negated with a `NOT` before the keyword.
+fn parse_predicate(pair: pest::iterators::Pair<Rule>) -> DbResult<Condition> {
+    let rule = pair.as_rule();
+    let mut inner = pair.into_inner().peekable();
+    let left = parse_expr(inner.next().ok_or(DbError::SyntaxError("No expression in WHERE".into()))?)?;
+    let negated = inner.next_if(|p| p.as_rule() == Rule::not_op).is_some();
+    let keyword = inner.next().ok_or(DbError::SyntaxError("No keyword in WHERE".into()))?;
+    let mut operands = inner.filter(|p| p.as_rule() != Rule::and_op)
+        .map(parse_expr)
+        .collect::<DbResult<Vec<Expr>>>()?;
+    let condition = match rule {
+        Rule::like_check => Condition::Like {
+            left: Box::new(left),
+            pattern: Box::new(operands.pop().ok_or(DbError::SyntaxError("No pattern in LIKE".into()))?),
+            case_insensitive: keyword.as_str() == "ILIKE",
+        },
+        Rule::in_check => Condition::In {left: Box::new(left), list: operands},
+        _ => {
+            let high = operands.pop().ok_or(DbError::SyntaxError("No upper bound in BETWEEN".into()))?;
+            let low = operands.pop().ok_or(DbError::SyntaxError("No lower bound in BETWEEN".into()))?;
+            Condition::Between {left: Box::new(left), low: Box::new(low), high: Box::new(high)}
+        }
+    };
+    Ok(if negated { Condition::Not(Box::new(condition)) } else { condition })
 }
 
 fn parse_value(pair: pest::iterators::Pair<Rule>) -> DbResult<Value> {
@@ -95,6 +477,7 @@ fn parse_value(pair: pest::iterators::Pair<Rule>) -> DbResult<Value> {
         Rule::float_w => inner.as_str().parse().map(Value::Float)
             .map_err(|_| DbError::SyntaxError("Bad Float".into())),
         Rule::bool_w => Ok(Value::Bool(inner.as_str()=="true")),
+        Rule::null_w => Ok(Value::Null),
         Rule::string_w => {
             let s = inner.as_str();
             if s.len()>=2 { Ok(Value::String(s[1..s.len()-1].to_string())) }
@@ -108,56 +491,192 @@ fn parse_create_command(pair: pest::iterators::Pair<Rule>) -> DbResult<Query> {
     let mut inner = pair.into_inner();
     let table= inner.next().map(|x| x.as_str().to_string())
         .ok_or(DbError::SyntaxError("No table in CREATE".into()))?;
-    let pk = inner.next().map(|x| x.as_str().to_string())
-        .ok_or(DbError::SyntaxError("No primary key in CREATE".into()))?;
+    let pk = inner.next().ok_or(DbError::SyntaxError("No primary key in CREATE".into()))?;
+    let pk: Vec<String> = match pk.as_rule() {
+        Rule::key_columns => pk.into_inner().map(|x| x.as_str().to_string()).collect(),
+        _ => vec![pk.as_str().to_string()],
+    };
 
     let mut cols = Vec::new();
     for column in inner {
-        let mut definiftion = column.into_inner();
-        let name = definiftion.next().map(|x| x.as_str().to_string())
-            .ok_or(DbError::SyntaxError("No name for column in CREATE".into()))?;
-        let typ = definiftion.next().map(|x| x.as_str())
-            .ok_or(DbError::SyntaxError("No type in CREATE".into()))?;
-        let dtype = match typ {
-            "Int" => DataType::Int,
-            "Float" => DataType::Float,
-            "Bool" => DataType::Bool,
-            "String" => DataType::String,
-            _ => return Err(DbError::SyntaxError("Unknown type in CREATE".into())),
-        };
-        cols.push((name, dtype));
+        cols.push(parse_column(column, &pk)?);
     }
     Ok(Query::Create {table, pk, columns: cols})
 }
 
+fn parse_type(pair: pest::iterators::Pair<Rule>) -> DbResult<DataType> {
+    match pair.as_str() {
+        "Int" => Ok(DataType::Int),
+        "Float" => Ok(DataType::Float),
+        "Bool" => Ok(DataType::Bool),
+        "String" => Ok(DataType::String),
+        _ => Err(DbError::SyntaxError("Unknown type of column".into())),
+    }
+}
+
+/// Parses a column declaration; `pk` names the primary-key columns when the column is part of CREATE.
+fn parse_column(pair: pest::iterators::Pair<Rule>, pk: &[String]) -> DbResult<ColumnDef> {
+    let mut definiftion = pair.into_inner();
+    let name = definiftion.next().map(|x| x.as_str().to_string())
+        .ok_or(DbError::SyntaxError("No name for column".into()))?;
+    let typ = definiftion.next()
+        .ok_or(DbError::SyntaxError("No type for column".into()))?;
+    let dtype = parse_type(typ)?;
+    let mut column = ColumnDef::new(name, dtype);
+    for option in definiftion {
+        match option.as_rule() {
+            Rule::nullable if pk.contains(&column.name) => {
+                return Err(DbError::SyntaxError("Primary key cannot be NULL".into()));
+            }
+            Rule::not_null => column.nullable = false,
+            Rule::default_value => {
+                let value = option.into_inner().next()
+                    .ok_or(DbError::SyntaxError("No value after DEFAULT".into()))?;
+                column.default = Some(parse_value(value)?);
+            }
+            Rule::auto_increment => column.auto_increment = true,
+            _ => {}
+        }
+    }
+    Ok(column)
+}
+
+fn parse_alter_command(pair: pest::iterators::Pair<Rule>) -> DbResult<Query> {
+    let mut inner = pair.into_inner();
+    let table = inner.next().map(|x| x.as_str().to_string())
+        .ok_or(DbError::SyntaxError("No table in ALTER TABLE".into()))?;
+    let action = inner.next().ok_or(DbError::SyntaxError("No action in ALTER TABLE".into()))?;
+    let rule = action.as_rule();
+    let mut names = action.into_inner()
+        .filter(|p| !matches!(p.as_rule(), Rule::column_kw | Rule::to_kw));
+    let mut next = |what: &str| names.next()
+        .ok_or_else(|| DbError::SyntaxError(format!("No {} in ALTER TABLE", what)));
+    let action = match rule {
+        Rule::add_column => AlterAction::AddColumn(parse_column(next("column")?, &[])?),
+        Rule::drop_column => AlterAction::DropColumn(next("column")?.as_str().to_string()),
+        Rule::rename_column => {
+            let from = next("column")?.as_str().to_string();
+            AlterAction::RenameColumn {from, to: next("new name")?.as_str().to_string()}
+        }
+        Rule::rename_table => AlterAction::RenameTable(next("new name")?.as_str().to_string()),
+        _ => return Err(DbError::SyntaxError("Unknown ALTER TABLE action".into())),
+    };
+    Ok(Query::AlterTable {table, action})
+}
+
+fn parse_create_index_command(pair: pest::iterators::Pair<Rule>) -> DbResult<Query> {
+    let mut inner = pair.into_inner();
+    let name = inner.next().map(|x| x.as_str().to_string())
+        .ok_or(DbError::SyntaxError("No index name in CREATE INDEX".into()))?;
+    let table = inner.next().map(|x| x.as_str().to_string())
+        .ok_or(DbError::SyntaxError("No table in CREATE INDEX".into()))?;
+    let column = inner.next().map(|x| x.as_str().to_string())
+        .ok_or(DbError::SyntaxError("No column in CREATE INDEX".into()))?;
+    Ok(Query::CreateIndex {name, table, column})
+}
+
 fn parse_delete_command(pair: pest::iterators::Pair<Rule>) -> DbResult<Query> {
     let mut inner = pair.into_inner();
     let v = inner.next().ok_or(DbError::SyntaxError("No value in DELETE".into()))?;
     let table = inner.next().map(|x| x.as_str().to_string())
         .ok_or(DbError::SyntaxError("No table in DELETE".into()))?;
-    let value = parse_value(v)?;
-    Ok(Query::Delete {table, key_value: value})
+    let key = match v.as_rule() {
+        Rule::key_tuple => v.into_inner().map(parse_value).collect::<DbResult<Vec<_>>>()?,
+        _ => vec![parse_value(v)?],
+    };
+    Ok(Query::Delete {table, key})
+}
+
+fn parse_delete_where_command(pair: pest::iterators::Pair<Rule>) -> DbResult<Query> {
+    let mut inner = pair.into_inner();
+    let table = inner.next().map(|x| x.as_str().to_string())
+        .ok_or(DbError::SyntaxError("No table in DELETE".into()))?;
+    let condition = inner.next().map(parse_where).transpose()?;
+    Ok(Query::DeleteWhere {table, condition})
 }
 
 fn parse_insert_command(pair: pest::iterators::Pair<Rule>) -> DbResult<Query> {
     let inner = pair.into_inner();
+    let mut rows = Vec::new();
     let mut values = Vec::new();
     let mut table = None;
+    let mut replace = false;
+    let mut on_conflict = OnConflict::Fail;
     for p in inner {
         match p.as_rule() {
-            Rule::assigment => {
-                let mut a = p.into_inner();
-                let column = a.next().map(|x| x.as_str().to_string())
-                    .ok_or(DbError::SyntaxError("No column name in INSERT".into()))?;
-                let value = parse_value(a.next()
-                    .ok_or(DbError::SyntaxError("No value in INSERT".into()))?)?;
-                values.push((column, value));
-            }
+            Rule::replace_kw => replace = true,
+            Rule::assigment => values.push(parse_assignment(p, "INSERT")?),
+            Rule::insert_row => rows.push(
+                p.into_inner().map(|a| parse_assignment(a, "INSERT")).collect::<DbResult<Vec<_>>>()?
+            ),
             Rule::ident => table = Some(p.as_str().to_string()),
+            Rule::on_conflict => on_conflict = parse_on_conflict(p)?,
             _ => return Err(DbError::SyntaxError("Unknown syntax of INSERT".into())),
         }
     }
-    Ok(Query::Insert {table: table.ok_or(DbError::SyntaxError("No table name in INSERT".into()))?, values})
+    if !values.is_empty() {
+        rows.push(values);
+    }
+    let table = table.ok_or(DbError::SyntaxError("No table name in INSERT".into()))?;
+    Ok(Query::Insert {table, rows, on_conflict: conflict_mode(replace, on_conflict)?})
+}
+
+fn parse_insert_select_command(pair: pest::iterators::Pair<Rule>) -> DbResult<Query> {
+    let mut inner = pair.into_inner().peekable();
+    let replace = inner.next_if(|p| p.as_rule() == Rule::replace_kw).is_some();
+    let table = inner.next().map(|x| x.as_str().to_string())
+        .ok_or(DbError::SyntaxError("No table name in INSERT".into()))?;
+    let select = inner.next().ok_or(DbError::SyntaxError("No SELECT in INSERT".into()))?;
+    let on_conflict = inner.next().map(parse_on_conflict).transpose()?.unwrap_or(OnConflict::Fail);
+    Ok(Query::InsertSelect {
+        table,
+        select: Box::new(parse_select_command(select)?),
+        on_conflict: conflict_mode(replace, on_conflict)?,
+    })
+}
+
+fn parse_on_conflict(pair: pest::iterators::Pair<Rule>) -> DbResult<OnConflict> {
+    let action = pair.into_inner().next().ok_or(DbError::SyntaxError("No action after ON CONFLICT".into()))?;
+    match action.as_rule() {
+        Rule::do_update => Ok(OnConflict::DoUpdate(
+            action.into_inner().map(|a| parse_assignment(a, "ON CONFLICT")).collect::<DbResult<Vec<_>>>()?
+        )),
+        _ => Ok(OnConflict::DoNothing),
+    }
+}
+
+/// REPLACE already says what to do with a taken key, so it cannot be combined with ON CONFLICT.
+fn conflict_mode(replace: bool, on_conflict: OnConflict) -> DbResult<OnConflict> {
+    match (replace, on_conflict) {
+        (false, on_conflict) => Ok(on_conflict),
+        (true, OnConflict::Fail) => Ok(OnConflict::Replace),
+        (true, _) => Err(DbError::SyntaxError("REPLACE cannot be combined with ON CONFLICT".into())),
+    }
+}
+
+fn parse_update_command(pair: pest::iterators::Pair<Rule>) -> DbResult<Query> {
+    let mut inner = pair.into_inner();
+    let table = inner.next().map(|x| x.as_str().to_string())
+        .ok_or(DbError::SyntaxError("No table name in UPDATE".into()))?;
+    let mut values = Vec::new();
+    let mut cond = None;
+    for p in inner {
+        match p.as_rule() {
+            Rule::assigment => values.push(parse_assignment(p, "UPDATE")?),
+            Rule::where_clause => cond = Some(parse_where(p)?),
+            _ => return Err(DbError::SyntaxError("Unknown syntax of UPDATE".into())),
+        }
+    }
+    Ok(Query::Update {table, values, condition: cond})
+}
+
+fn parse_assignment(pair: pest::iterators::Pair<Rule>, cmd: &str) -> DbResult<(String, Value)> {
+    let mut a = pair.into_inner();
+    let column = a.next().map(|x| x.as_str().to_string())
+        .ok_or(DbError::SyntaxError(format!("No column name in {}", cmd)))?;
+    let value = parse_value(a.next()
+        .ok_or(DbError::SyntaxError(format!("No value in {}", cmd)))?)?;
+    Ok((column, value))
 }
 
 
@@ -169,18 +688,250 @@ mod tests {
     fn test_parse_select_command() {
         let input = "SELECT job, height, age FROM people WHERE sex = \"male\"";
         match parse(input) {
-            Ok(Query::Select { table, fields, condition }) => {
+            Ok(Query::Select { table, fields, condition, .. }) => {
                 assert_eq!(table, "people");
-                assert_eq!(fields, vec!["job", "height", "age"]);
-                if let Some(c) = condition {
-                    assert_eq!(c.column, "sex");
-                    assert_eq!(c.operator, Operator::Equal);
-                    assert_eq!(c.value, Value::String("male".into()));
-                } else {
-                    assert!(false, "No where clause");
+                assert_eq!(fields, vec!["job".into(), "height".into(), "age".into()]);
+                match condition {
+                    Some(Condition::Comparison { column, operator, value }) => {
+                        assert_eq!(column, "sex");
+                        assert_eq!(operator, Operator::Equal);
+                        assert_eq!(value, Value::String("male".into()));
+                    }
+                    _ => panic!("No where clause"),
                 }
             }
-            _ => assert!(false, "SELECT parsing error"),
+            _ => panic!("SELECT parsing error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_select_order_limit_offset() {
+        let input = "SELECT job, age FROM people WHERE age > 20 ORDER BY age DESC, job LIMIT 10 OFFSET 5";
+        match parse(input) {
+            Ok(Query::Select { fields, order_by, limit, offset, .. }) => {
+                assert_eq!(fields, vec!["job".into(), "age".into()]);
+                assert_eq!(order_by, vec![
+                    ("age".into(), SortOrder::Descending),
+                    ("job".into(), SortOrder::Ascending),
+                ]);
+                assert_eq!(limit, Some(10));
+                assert_eq!(offset, Some(5));
+            }
+            _ => panic!("SELECT with ORDER BY parsing error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_select_aggregates() {
+        let input = "SELECT topic, COUNT(*), AVG(rating) FROM library GROUP BY topic HAVING COUNT(*) > 1 ORDER BY AVG(rating) DESC";
+        let count = Aggregate { function: AggregateFunction::Count, column: None };
+        let avg = Aggregate { function: AggregateFunction::Avg, column: Some("rating".into()) };
+        match parse(input) {
+            Ok(Query::Select { fields, group_by, having, order_by, .. }) => {
+                assert_eq!(fields, vec![
+                    "topic".into(),
+                    SelectItem::Aggregate(count.clone()).into(),
+                    SelectItem::Aggregate(avg.clone()).into(),
+                ]);
+                assert_eq!(group_by, vec!["topic"]);
+                assert_eq!(having, Some(Condition::AggregateComparison {
+                    aggregate: count,
+                    operator: Operator::GreaterThan,
+                    value: Value::Int(1),
+                }));
+                assert_eq!(order_by, vec![(SelectItem::Aggregate(avg), SortOrder::Descending)]);
+            }
+            _ => panic!("SELECT with aggregates parsing error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_aggregate_in_where_fails() {
+        match parse("SELECT topic FROM library WHERE COUNT(*) > 1") {
+            Err(DbError::SyntaxError(_)) => {}
+            other => panic!("SyntaxError expected, got: {:?}", other),
+        }
+        match parse("SELECT SUM(*) FROM library") {
+            Err(DbError::SyntaxError(_)) => {}
+            other => panic!("SyntaxError expected, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_select_wildcard_and_aliases() {
+        let input = "SELECT *, title AS name, COUNT(*) AS total FROM library";
+        match parse(input) {
+            Ok(Query::Select { fields, .. }) => {
+                assert_eq!(fields, vec![
+                    SelectItem::Wildcard.into(),
+                    SelectField { item: "title".into(), alias: Some("name".into()) },
+                    SelectField {
+                        item: SelectItem::Aggregate(Aggregate { function: AggregateFunction::Count, column: None }),
+                        alias: Some("total".into()),
+                    },
+                ]);
+                assert_eq!(fields[1].name(), "name");
+            }
+            _ => panic!("SELECT with aliases parsing error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_select_join() {
+        let input = "SELECT library.title, concepts.name FROM library LEFT JOIN concepts ON concepts.introduced_year = library.year WHERE library.pages > 250";
+        match parse(input) {
+            Ok(Query::Select { table, joins, fields, .. }) => {
+                assert_eq!(table, "library");
+                assert_eq!(fields, vec!["library.title".into(), "concepts.name".into()]);
+                assert_eq!(joins, vec![Join {
+                    kind: JoinKind::Left,
+                    table: "concepts".into(),
+                    left_column: "library.year".into(),
+                    right_column: "concepts.introduced_year".into(),
+                }]);
+            }
+            _ => panic!("SELECT with JOIN parsing error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_compound_where() {
+        let input = "SELECT job FROM people WHERE NOT (age > 20 OR sex = \"male\") AND height < 180.0";
+        let comparison = |column: &str, operator, value| Box::new(Condition::Comparison {
+            column: column.into(), operator, value,
+        });
+        match parse(input) {
+            Ok(Query::Select { condition: Some(condition), .. }) => {
+                let expected = Condition::And(
+                    Box::new(Condition::Not(Box::new(Condition::Or(
+                        comparison("age", Operator::GreaterThan, Value::Int(20)),
+                        comparison("sex", Operator::Equal, Value::String("male".into())),
+                    )))),
+                    comparison("height", Operator::LessThan, Value::Float(180.0)),
+                );
+                assert_eq!(condition, expected);
+            }
+            _ => panic!("Compound WHERE parsing error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_where_precedence() {
+        let input = "SELECT job FROM people WHERE age = 1 OR age = 2 AND NOTE = 3";
+        match parse(input) {
+            Ok(Query::Select { condition: Some(Condition::Or(left, right)), .. }) => {
+                assert!(matches!(*left, Condition::Comparison { .. }));
+                match *right {
+                    Condition::And(_, note) => assert!(
+                        matches!(*note, Condition::Comparison { ref column, .. } if column == "NOTE")
+                    ),
+                    other => panic!("AND expected on the right side of OR, got: {:?}", other),
+                }
+            }
+            other => panic!("WHERE precedence parsing error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_expressions() {
+        let input = "SELECT title || \"!\" AS loud, -pages FROM books WHERE rating * 2 > pages / 100 AND year > -5";
+        let column = |name: &str| Expr::Column(name.into());
+        let int = |n: i64| Expr::Literal(Value::Int(n));
+        match parse(input) {
+            Ok(Query::Select { fields, condition: Some(Condition::And(left, right)), .. }) => {
+                let bang = Expr::Literal(Value::String("!".into()));
+                let loud = Expr::binary(column("title"), BinaryOperator::Concat, bang);
+                assert_eq!(fields[0], SelectField { item: SelectItem::Expression(loud), alias: Some("loud".into()) });
+                assert_eq!(fields[1].item, SelectItem::Expression(Expr::Negate(Box::new(column("pages")))));
+                assert_eq!(*left, Condition::Compare {
+                    left: Box::new(Expr::binary(column("rating"), BinaryOperator::Multiply, int(2))),
+                    operator: Operator::GreaterThan,
+                    right: Box::new(Expr::binary(column("pages"), BinaryOperator::Divide, int(100))),
+                });
+                assert_eq!(*right, Condition::Comparison {
+                    column: "year".into(), operator: Operator::GreaterThan, value: Value::Int(-5),
+                });
+            }
+            other => panic!("Expression parsing error: {:?}", other),
+        }
+
+        match parse("SELECT a FROM t ORDER BY a - b * (c + 1) DESC") {
+            Ok(Query::Select { order_by, .. }) => {
+                let sum = Expr::binary(column("c"), BinaryOperator::Add, int(1));
+                let product = Expr::binary(column("b"), BinaryOperator::Multiply, sum);
+                let expected = Expr::binary(column("a"), BinaryOperator::Subtract, product);
+                assert_eq!(order_by, vec![(SelectItem::Expression(expected), SortOrder::Descending)]);
+            }
+            other => panic!("ORDER BY expression parsing error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_like_in_between() {
+        let input = "SELECT id FROM books WHERE title ILIKE \"%type%\" AND topic NOT IN (\"Cubical\", \"Synthetic\") \
+                     AND year BETWEEN 2013 AND 2020";
+        let column = |name: &str| Expr::Column(name.into());
+        let text = |s: &str| Expr::Literal(Value::String(s.into()));
+        match parse(input) {
+            Ok(Query::Select { condition: Some(Condition::And(left, between)), .. }) => {
+                let expected = Condition::And(
+                    Box::new(Condition::Like {
+                        left: Box::new(column("title")),
+                        pattern: Box::new(text("%type%")),
+                        case_insensitive: true,
+                    }),
+                    Box::new(Condition::Not(Box::new(Condition::In {
+                        left: Box::new(column("topic")),
+                        list: vec![text("Cubical"), text("Synthetic")],
+                    }))),
+                );
+                assert_eq!(*left, expected);
+                assert_eq!(*between, Condition::Between {
+                    left: Box::new(column("year")),
+                    low: Box::new(Expr::Literal(Value::Int(2013))),
+                    high: Box::new(Expr::Literal(Value::Int(2020))),
+                });
+            }
+            other => panic!("LIKE, IN and BETWEEN parsing error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_functions_case_and_cast() {
+        let input = "SELECT UPPER(SUBSTR(title, 1, 3)), \
+                     CASE WHEN pages > 500 THEN \"long\" ELSE \"short\" END AS size, CAST(pages AS String) FROM books WHERE COALESCE(rating, 0) >= 4";
+        let column = |name: &str| Expr::Column(name.into());
+        let int = |n: i64| Expr::Literal(Value::Int(n));
+        let text = |s: &str| Expr::Literal(Value::String(s.into()));
+        match parse(input) {
+            Ok(Query::Select { fields, condition: Some(condition), .. }) => {
+                let substr = Expr::Function { name: "SUBSTR".into(), args: vec![column("title"), int(1), int(3)] };
+                let upper = Expr::Function { name: "UPPER".into(), args: vec![substr] };
+                let case = Expr::Case {
+                    branches: vec![(
+                        Condition::Comparison {
+                            column: "pages".into(),
+                            operator: Operator::GreaterThan,
+                            value: Value::Int(500),
+                        },
+                        text("long"),
+                    )],
+                    otherwise: Some(Box::new(text("short"))),
+                };
+                let cast = Expr::Cast { expr: Box::new(column("pages")), data_type: DataType::String };
+                let items: Vec<SelectItem> = fields.into_iter().map(|field| field.item).collect();
+                assert_eq!(items, vec![
+                    SelectItem::Expression(upper),
+                    SelectItem::Expression(case),
+                    SelectItem::Expression(cast),
+                ]);
+                assert_eq!(condition, Condition::Compare {
+                    left: Box::new(Expr::Function { name: "COALESCE".into(), args: vec![column("rating"), int(0)] }),
+                    operator: Operator::GreaterThanOrEqual,
+                    right: Box::new(int(4)),
+                });
+            }
+            other => panic!("Function parsing error: {:?}", other),
         }
     }
 
@@ -190,38 +941,232 @@ mod tests {
         match parse(input) {
             Ok(Query::Create { table, pk, columns }) => {
                 assert_eq!(table, "people");
-                assert_eq!(pk, "id");
+                assert_eq!(pk, ["id"]);
                 assert_eq!(columns.len(), 3);
             }
-            _ => assert!(false, "CREATE parsing error"),
+            _ => panic!("CREATE parsing error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_composite_key() {
+        match parse("CREATE visits KEY (person, day) FIELDS person:Int, day:String, note:String NULL") {
+            Ok(Query::Create { pk, .. }) => assert_eq!(pk, ["person", "day"]),
+            other => panic!("CREATE parsing error: {:?}", other),
+        }
+        assert!(matches!(parse("CREATE visits KEY (person, day) FIELDS person:Int NULL, day:String"), Err(DbError::SyntaxError(_))));
+        match parse("DELETE (1, \"mon\") FROM visits") {
+            Ok(Query::Delete { table, key }) => {
+                assert_eq!(table, "visits");
+                assert_eq!(key, [Value::Int(1), Value::String("mon".into())]);
+            }
+            other => panic!("DELETE parsing error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_create_nullability() {
+        match parse("CREATE people KEY id FIELDS id:Int, job:String NOT NULL, age:Int NULL, note:String") {
+            Ok(Query::Create { columns, .. }) => {
+                let nullable: Vec<bool> = columns.iter().map(|c| c.nullable).collect();
+                assert_eq!(nullable, [true, false, true, true]);
+            }
+            other => panic!("CREATE parsing error: {:?}", other),
+        }
+        assert!(matches!(parse("CREATE people KEY id FIELDS id:Int NULL"), Err(DbError::SyntaxError(_))));
+    }
+
+    #[test]
+    fn test_parse_create_default_and_auto_increment() {
+        match parse("CREATE people KEY id FIELDS id:Int AUTO_INCREMENT, job:String NOT NULL DEFAULT \"none\", age:Int") {
+            Ok(Query::Create { columns, .. }) => {
+                assert_eq!(columns[0], ColumnDef::new("id", DataType::Int).auto_increment());
+                assert_eq!(columns[1], ColumnDef::new("job", DataType::String).not_null().default(Value::String("none".into())));
+                assert_eq!(columns[2], ColumnDef::new("age", DataType::Int));
+            }
+            other => panic!("CREATE parsing error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_null_literal_and_is_null() {
+        match parse("INSERT id=1, age=NULL INTO people") {
+            Ok(Query::Insert { rows, .. }) => assert_eq!(rows[0][1], ("age".to_string(), Value::Null)),
+            other => panic!("INSERT with NULL parsing error: {:?}", other),
+        }
+        match parse("SELECT id FROM people WHERE age IS NULL OR NOT job IS NOT NULL") {
+            Ok(Query::Select { condition: Some(condition), .. }) => {
+                let expected = Condition::Or(
+                    Box::new(Condition::IsNull { column: "age".into(), negated: false }),
+                    Box::new(Condition::Not(Box::new(Condition::IsNull { column: "job".into(), negated: true }))),
+                );
+                assert_eq!(condition, expected);
+            }
+            other => panic!("IS NULL parsing error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_drop_and_truncate() {
+        for (input, expected) in [("DROP TABLE people", false), ("DROP TABLE IF EXISTS people", true)] {
+            match parse(input) {
+                Ok(Query::DropTable { table, if_exists }) => {
+                    assert_eq!(table, "people");
+                    assert_eq!(if_exists, expected);
+                }
+                other => panic!("Parsing '{}' failed: {:?}", input, other),
+            }
+        }
+        assert!(matches!(parse("TRUNCATE people"), Ok(Query::Truncate { table }) if table == "people"));
+        assert!(matches!(parse("TRUNCATE TABLE tables"), Ok(Query::Truncate { table }) if table == "tables"));
+    }
+
+    #[test]
+    fn test_parse_alter() {
+        let cases = [
+            ("ALTER TABLE people ADD COLUMN age:Int DEFAULT 18",
+                AlterAction::AddColumn(ColumnDef::new("age", DataType::Int).default(Value::Int(18)))),
+            ("ALTER TABLE people ADD note:String NOT NULL",
+                AlterAction::AddColumn(ColumnDef::new("note", DataType::String).not_null())),
+            ("ALTER TABLE people DROP COLUMN age", AlterAction::DropColumn("age".into())),
+            ("ALTER TABLE people DROP columns", AlterAction::DropColumn("columns".into())),
+            ("ALTER TABLE people RENAME COLUMN job TO occupation",
+                AlterAction::RenameColumn { from: "job".into(), to: "occupation".into() }),
+            ("ALTER TABLE people RENAME total TO top",
+                AlterAction::RenameColumn { from: "total".into(), to: "top".into() }),
+            ("ALTER TABLE people RENAME TO persons", AlterAction::RenameTable("persons".into())),
+        ];
+        for (input, expected) in cases {
+            match parse(input) {
+                Ok(Query::AlterTable { table, action }) => {
+                    assert_eq!(table, "people");
+                    assert_eq!(action, expected, "Wrong action for '{}'", input);
+                }
+                other => panic!("Parsing '{}' failed: {:?}", input, other),
+            }
         }
     }
 
+    #[test]
+    fn test_parse_show_tables_and_describe() {
+        assert!(matches!(parse("SHOW TABLES"), Ok(Query::ShowTables)));
+        assert!(matches!(parse("DESCRIBE people"), Ok(Query::Describe { table }) if table == "people"));
+    }
+
     #[test]
     fn test_parse_delete() {
         let input = "DELETE 100 FROM people";
         match parse(input) {
-            Ok(Query::Delete { table, key_value}) => {
+            Ok(Query::Delete { table, key }) => {
                 assert_eq!(table, "people");
-                assert_eq!(key_value, Value::Int(100));
+                assert_eq!(key, [Value::Int(100)]);
             }
-            _ => assert!(false, "DELETE parsing error"),
+            _ => panic!("DELETE parsing error"),
         }
     }
 
+    #[test]
+    fn test_parse_delete_where() {
+        match parse("DELETE FROM books WHERE pages > 250") {
+            Ok(Query::DeleteWhere { table, condition: Some(Condition::Comparison { column, operator, value }) }) => {
+                assert_eq!(table, "books");
+                assert_eq!(column, "pages");
+                assert_eq!(operator, Operator::GreaterThan);
+                assert_eq!(value, Value::Int(250));
+            }
+            other => panic!("DELETE parsing error: {:?}", other),
+        }
+        assert!(matches!(parse("DELETE FROM books"), Ok(Query::DeleteWhere { condition: None, .. })));
+    }
+
     #[test]
     fn test_parse_insert() {
         let input = "INSERT id=1, job=\"fire fighter\", height=180.5 INTO people";
         match parse(input) {
-            Ok(Query::Insert { table, values }) => {
+            Ok(Query::Insert { table, rows, on_conflict }) => {
                 assert_eq!(table, "people");
-                assert_eq!(values.len(), 3);
-                match &values[2].1 {
+                assert_eq!(on_conflict, OnConflict::Fail);
+                assert_eq!(rows.len(), 1);
+                assert_eq!(rows[0].len(), 3);
+                match &rows[0][2].1 {
                     Value::Float(f) => assert!((f - 180.5).abs() < f64::EPSILON),
-                    _ => assert!(false, "Float was expected"),
+                    _ => panic!("Float was expected"),
                 }
             }
-            _ => assert!(false, "INSERT parsing error"),
+            _ => panic!("INSERT parsing error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_insert_many_and_select() {
+        match parse("INSERT (id=1, job=\"actor\"), (id=2) INTO people") {
+            Ok(Query::Insert { table, rows, .. }) => {
+                assert_eq!(table, "people");
+                assert_eq!(rows.len(), 2);
+                assert_eq!(rows[1], [("id".to_string(), Value::Int(2))]);
+            }
+            other => panic!("INSERT parsing error: {:?}", other),
+        }
+        match parse("INSERT INTO archive SELECT id, job FROM people WHERE id > 1") {
+            Ok(Query::InsertSelect { table, select, .. }) => {
+                assert_eq!(table, "archive");
+                assert!(matches!(*select, Query::Select { ref table, .. } if table == "people"));
+            }
+            other => panic!("INSERT parsing error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_upserts() {
+        let conflict = |input: &str| match parse(input) {
+            Ok(Query::Insert { on_conflict, .. } | Query::InsertSelect { on_conflict, .. }) => on_conflict,
+            other => panic!("INSERT parsing error: {:?}", other),
+        };
+        assert_eq!(conflict("INSERT id=1 INTO people ON CONFLICT DO NOTHING"), OnConflict::DoNothing);
+        assert_eq!(
+            conflict("INSERT (id=1), (id=2) INTO people ON CONFLICT DO UPDATE SET job = \"cook\", age = 3"),
+            OnConflict::DoUpdate(vec![("job".into(), Value::String("cook".into())), ("age".into(), Value::Int(3))])
+        );
+        assert_eq!(conflict("REPLACE id=1, job=\"cook\" INTO people"), OnConflict::Replace);
+        assert_eq!(conflict("REPLACE INTO archive SELECT id FROM people"), OnConflict::Replace);
+        assert_eq!(
+            conflict("INSERT INTO archive SELECT id FROM people WHERE id > 1 ON CONFLICT DO NOTHING"),
+            OnConflict::DoNothing
+        );
+        assert!(matches!(parse("REPLACE id=1 INTO people ON CONFLICT DO NOTHING"), Err(DbError::SyntaxError(_))));
+    }
+
+    #[test]
+    fn test_parse_update() {
+        let input = "UPDATE people SET job = \"teacher\", age = 31 WHERE id = 1";
+        match parse(input) {
+            Ok(Query::Update { table, values, condition }) => {
+                assert_eq!(table, "people");
+                assert_eq!(values.len(), 2);
+                assert_eq!(values[0], ("job".to_string(), Value::String("teacher".into())));
+                assert_eq!(values[1], ("age".to_string(), Value::Int(31)));
+                match condition {
+                    Some(Condition::Comparison { column, .. }) => assert_eq!(column, "id"),
+                    _ => panic!("No where clause"),
+                }
+            }
+            _ => panic!("UPDATE parsing error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_create_and_drop_index() {
+        match parse("CREATE INDEX people_job ON people(job)") {
+            Ok(Query::CreateIndex { name, table, column }) => {
+                assert_eq!(name, "people_job");
+                assert_eq!(table, "people");
+                assert_eq!(column, "job");
+            }
+            other => panic!("CREATE INDEX parsing error: {:?}", other),
+        }
+        match parse("DROP INDEX people_job") {
+            Ok(Query::DropIndex { name }) => assert_eq!(name, "people_job"),
+            other => panic!("DROP INDEX parsing error: {:?}", other),
         }
     }
 
@@ -230,7 +1175,7 @@ mod tests {
         let input = "SAVE_AS backup.db";
         match parse(input) {
             Ok(Query::SaveAs(path)) => assert_eq!(path, "backup.db"),
-            _ => assert!(false, "SAVE_AS parsing error"),
+            _ => panic!("SAVE_AS parsing error"),
         }
     }
 
@@ -238,11 +1183,32 @@ mod tests {
     fn test_parse_read() {
         let input = "READ_FROM init.sql";
         match parse(input) {
-            Ok(Query::ReadFrom(path)) => assert_eq!(path, "init.sql"),
-            _ => assert!(false, "READ_FROM parsing error"),
+            Ok(Query::ReadFrom { path, atomic }) => {
+                assert_eq!(path, "init.sql");
+                assert!(!atomic);
+            }
+            _ => panic!("READ_FROM parsing error"),
         }
     }
 
+    #[test]
+    fn test_parse_read_atomic() {
+        match parse("READ_FROM scripts/init.sql ATOMIC") {
+            Ok(Query::ReadFrom { path, atomic }) => {
+                assert_eq!(path, "scripts/init.sql");
+                assert!(atomic);
+            }
+            other => panic!("READ_FROM ATOMIC parsing error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_transaction_statements() {
+        assert!(matches!(parse("BEGIN"), Ok(Query::Begin)));
+        assert!(matches!(parse("COMMIT"), Ok(Query::Commit)));
+        assert!(matches!(parse("ROLLBACK"), Ok(Query::Rollback)));
+    }
+
     #[test]
     fn test_parse_invalid_syntax() {
         let input = "CREATE TABLE without KEY keyword";
@@ -250,9 +1216,9 @@ mod tests {
         let result = parse(input);
 
         match result {
-            Err(DbError::SyntaxError(_)) => assert!(true),
-            Ok(_) => assert!(false, "Parser should have failed"),
-            Err(e) => assert!(false, "SyntaxError expected, got: {:?}", e),
+            Err(DbError::SyntaxError(_)) => {}
+            Ok(_) => panic!("Parser should have failed"),
+            Err(e) => panic!("SyntaxError expected, got: {:?}", e),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,36 @@
+use crate::domain::{DataType, Value};
+
+/// Rows produced by a query together with the name and type of every column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultSet {
+    pub columns: Vec<String>,
+    pub types: Vec<DataType>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl ResultSet {
+    pub fn new(columns: Vec<String>, types: Vec<DataType>) -> ResultSet {
+        Self {
+            columns,
+            types,
+            rows: Vec::new(),
+        }
+    }
+
+    /// Returns the value of `column` in the row at `row`, if both exist.
+    pub fn get(&self, row: usize, column: &str) -> Option<&Value> {
+        let index = self.columns.iter().position(|c| c == column)?;
+        self.rows.get(row).and_then(|r| r.get(index))
+    }
+}
+
+/// The outcome of executing a single query.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryResult {
+    /// Data returned by a SELECT.
+    Rows(ResultSet),
+    /// Number of records inserted, updated or deleted.
+    RowsAffected(usize),
+    /// Confirmation of a schema change.
+    Message(String),
+}
@@ -0,0 +1,542 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use crate::database::Table;
+use crate::domain::{ColumnDef, DataType, DatabaseKey, Record, Value};
+use crate::error::{DbError, DbResult};
+
+// Every file starts with a magic number and a format version, followed by the length of the
+// payload, the payload itself and a CRC-32 of the payload. All integers are little endian.
+// Table files carry the generation of the snapshot they belong to in their name, and the
+// catalog names the current generation, so replacing the catalog switches every table at once.
+const CATALOG_MAGIC: &[u8; 4] = b"RDBC";
+const TABLE_MAGIC: &[u8; 4] = b"RDBT";
+pub(crate) const FORMAT_VERSION: u16 = 1;
+
+pub const CATALOG_FILE: &str = "catalog.rdb";
+const TABLE_EXTENSION: &str = "tbl";
+
+/// Writes the catalog and one data file per table into `dir`, creating it if needed.
+/// `lsn` is the last write-ahead log frame whose changes the tables contain. A crash at any
+/// point leaves either the previous snapshot or the new one, never a mix of both.
+pub fn save_tables<'a, K: DatabaseKey + 'a>(
+    dir: &Path,
+    tables: impl Iterator<Item = &'a Table<K>>,
+    lsn: u64,
+) -> DbResult<()> {
+    fs::create_dir_all(dir)?;
+    let mut tables: Vec<&Table<K>> = tables.collect();
+    tables.sort_by(|a, b| a.name.cmp(&b.name));
+    let generation = current_generation(dir)? + 1;
+
+    let mut catalog = Encoder::new();
+    catalog.put_u64(lsn);
+    catalog.put_u64(generation);
+    catalog.put_u32(tables.len() as u32);
+    for table in &tables {
+        catalog.put_str(&table.name);
+        catalog.put_key_columns(table.pk_columns());
+        catalog.put_u32(table.columns().len() as u32);
+        for column in table.column_defs() {
+            catalog.put_column(&column);
+        }
+        catalog.put_u32(table.indexes().len() as u32);
+        for (name, index) in table.indexes() {
+            catalog.put_str(name);
+            catalog.put_str(index.column());
+        }
+        write_file(&dir.join(table_file(&table.name, generation)), TABLE_MAGIC, &encode_rows(table))?;
+    }
+    sync_dir(dir)?;
+    write_file(&dir.join(CATALOG_FILE), CATALOG_MAGIC, &catalog.bytes)?;
+    sync_dir(dir)?;
+
+    // Data files of earlier snapshots and of dropped tables would otherwise linger in the directory.
+    let current: HashSet<String> = tables.iter().map(|table| table_file(&table.name, generation)).collect();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_table = path.extension().is_some_and(|ext| ext == TABLE_EXTENSION);
+        let known = path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| current.contains(name));
+        if is_table && !known {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Generation of the snapshot stored in `dir`, or 0 if there is none.
+fn current_generation(dir: &Path) -> DbResult<u64> {
+    let catalog_path = dir.join(CATALOG_FILE);
+    if !catalog_path.exists() {
+        return Ok(0);
+    }
+    let payload = read_file(&catalog_path, CATALOG_MAGIC)?;
+    let mut catalog = Decoder::new(&payload);
+    catalog.u64()?;
+    catalog.u64()
+}
+
+/// Reads every table stored in `dir` together with the log sequence number saved with them.
+/// A directory without a catalog holds no tables.
+pub fn load_tables<K: DatabaseKey>(dir: &Path) -> DbResult<(Vec<Table<K>>, u64)> {
+    let catalog_path = dir.join(CATALOG_FILE);
+    if !catalog_path.exists() {
+        return Ok((Vec::new(), 0));
+    }
+
+    let payload = read_file(&catalog_path, CATALOG_MAGIC)?;
+    let mut catalog = Decoder::new(&payload);
+    let lsn = catalog.u64()?;
+    let generation = catalog.u64()?;
+    let count = catalog.u32()?;
+    let mut tables = Vec::new();
+    for _ in 0..count {
+        let name = catalog.string()?;
+        let pk_columns = catalog.key_columns()?;
+        let column_count = catalog.u32()?;
+        let mut columns = Vec::new();
+        for _ in 0..column_count {
+            columns.push(catalog.column()?);
+        }
+        let mut indexes = Vec::new();
+        let index_count = catalog.u32()?;
+        for _ in 0..index_count {
+            indexes.push((catalog.string()?, catalog.string()?));
+        }
+
+        let mut table = Table::new(name, columns, pk_columns);
+        let data = read_file(&dir.join(table_file(&table.name, generation)), TABLE_MAGIC)?;
+        for record in decode_rows(&data, table.columns())? {
+            table.insert(record)?;
+        }
+        for (name, column) in indexes {
+            table.create_index(name, column)?;
+        }
+        tables.push(table);
+    }
+    catalog.finish()?;
+    Ok((tables, lsn))
+}
+
+fn table_file(name: &str, generation: u64) -> String {
+    format!("{}.{}.{}", name, generation, TABLE_EXTENSION)
+}
+
+fn encode_rows<K: DatabaseKey>(table: &Table<K>) -> Vec<u8> {
+    let mut rows = Encoder::new();
+    rows.put_u64(table.store.len() as u64);
+    for record in table.scan() {
+        for column in table.columns() {
+            rows.put_value(record.fields.get(column).unwrap_or(&Value::Null));
+        }
+    }
+    rows.bytes
+}
+
+fn decode_rows(data: &[u8], columns: &[String]) -> DbResult<Vec<Record>> {
+    let mut rows = Decoder::new(data);
+    let count = rows.u64()?;
+    let mut records = Vec::new();
+    for _ in 0..count {
+        let mut fields = HashMap::new();
+        for column in columns {
+            fields.insert(column.clone(), rows.value()?);
+        }
+        records.push(Record { fields });
+    }
+    rows.finish()?;
+    Ok(records)
+}
+
+/// Writes the file next to its destination first and renames it into place, so a crash
+/// during the write never leaves a half-written file under the real name.
+fn write_file(path: &Path, magic: &[u8; 4], payload: &[u8]) -> DbResult<()> {
+    let mut contents = Vec::with_capacity(payload.len() + 18);
+    contents.extend_from_slice(magic);
+    contents.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    contents.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    contents.extend_from_slice(payload);
+    contents.extend_from_slice(&crc32(payload).to_le_bytes());
+
+    let tmp_path = path.with_extension("tmp");
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(&contents)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Forces the renames done in `dir` to disk.
+fn sync_dir(dir: &Path) -> DbResult<()> {
+    // Windows cannot open a directory as a file and needs no such sync.
+    if cfg!(unix) {
+        fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+fn read_file(path: &Path, magic: &[u8; 4]) -> DbResult<Vec<u8>> {
+    let contents = fs::read(path)?;
+    let name = path.display();
+    let mut decoder = Decoder::new(&contents);
+    if decoder.take(4)? != magic {
+        return Err(DbError::StorageError(format!("{} is not a database file", name)));
+    }
+    let version = decoder.u16()?;
+    if version != FORMAT_VERSION {
+        return Err(DbError::StorageError(format!("{} has unsupported format version {}", name, version)));
+    }
+    let length = decoder.u64()? as usize;
+    let payload = decoder.take(length)?.to_vec();
+    let checksum = decoder.u32()?;
+    decoder.finish()?;
+    if crc32(&payload) != checksum {
+        return Err(DbError::StorageError(format!("{} failed its checksum", name)));
+    }
+    Ok(payload)
+}
+
+pub(crate) fn type_tag(data_type: &DataType) -> u8 {
+    match data_type {
+        DataType::Bool => 0,
+        DataType::Int => 1,
+        DataType::Float => 2,
+        DataType::String => 3,
+    }
+}
+
+pub(crate) fn data_type(tag: u8) -> DbResult<DataType> {
+    match tag {
+        0 => Ok(DataType::Bool),
+        1 => Ok(DataType::Int),
+        2 => Ok(DataType::Float),
+        3 => Ok(DataType::String),
+        _ => Err(DbError::StorageError(format!("Unknown column type tag {}", tag))),
+    }
+}
+
+/// CRC-32 (IEEE 802.3), as used by zip and PNG.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+pub(crate) struct Encoder {
+    pub bytes: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Encoder {
+        Self { bytes: Vec::new() }
+    }
+
+    pub fn put_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn put_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn put_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn put_str(&mut self, value: &str) {
+        self.put_u32(value.len() as u32);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    pub fn put_column(&mut self, column: &ColumnDef) {
+        self.put_str(&column.name);
+        self.put_u8(type_tag(&column.data_type));
+        self.put_u8(column.nullable as u8);
+        match &column.default {
+            Some(value) => {
+                self.put_u8(1);
+                self.put_value(value);
+            }
+            None => self.put_u8(0),
+        }
+        self.put_u8(column.auto_increment as u8);
+    }
+
+    pub fn put_key_columns(&mut self, columns: &[String]) {
+        self.put_u32(columns.len() as u32);
+        for column in columns {
+            self.put_str(column);
+        }
+    }
+
+    pub fn put_value(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.put_u8(0),
+            Value::Bool(b) => {
+                self.put_u8(1);
+                self.put_u8(*b as u8);
+            }
+            Value::Int(i) => {
+                self.put_u8(2);
+                self.bytes.extend_from_slice(&i.to_le_bytes());
+            }
+            Value::Float(f) => {
+                self.put_u8(3);
+                self.bytes.extend_from_slice(&f.to_le_bytes());
+            }
+            Value::String(s) => {
+                self.put_u8(4);
+                self.put_str(s);
+            }
+        }
+    }
+}
+
+pub(crate) struct Decoder<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(data: &'a [u8]) -> Decoder<'a> {
+        Self { data, position: 0 }
+    }
+
+    pub fn take(&mut self, length: usize) -> DbResult<&'a [u8]> {
+        let end = self.position.checked_add(length)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(DbError::StorageError("Unexpected end of data".into()))?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> DbResult<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    pub fn u8(&mut self) -> DbResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> DbResult<u16> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> DbResult<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> DbResult<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub fn string(&mut self) -> DbResult<String> {
+        let length = self.u32()? as usize;
+        String::from_utf8(self.take(length)?.to_vec())
+            .map_err(|_| DbError::StorageError("Invalid UTF-8 in string".into()))
+    }
+
+    pub fn column(&mut self) -> DbResult<ColumnDef> {
+        let name = self.string()?;
+        let data_type = data_type(self.u8()?)?;
+        let nullable = self.u8()? != 0;
+        let mut column = ColumnDef { nullable, ..ColumnDef::new(name, data_type) };
+        if self.u8()? != 0 {
+            column.default = Some(self.value()?);
+        }
+        column.auto_increment = self.u8()? != 0;
+        Ok(column)
+    }
+
+    pub fn key_columns(&mut self) -> DbResult<Vec<String>> {
+        let count = self.u32()?;
+        let mut columns = Vec::new();
+        for _ in 0..count {
+            columns.push(self.string()?);
+        }
+        Ok(columns)
+    }
+
+    pub fn value(&mut self) -> DbResult<Value> {
+        match self.u8()? {
+            0 => Ok(Value::Null),
+            1 => Ok(Value::Bool(self.u8()? != 0)),
+            2 => Ok(Value::Int(i64::from_le_bytes(self.array()?))),
+            3 => Ok(Value::Float(f64::from_le_bytes(self.array()?))),
+            4 => Ok(Value::String(self.string()?)),
+            tag => Err(DbError::StorageError(format!("Unknown value tag {}", tag))),
+        }
+    }
+
+    /// Fails if any bytes were left unread.
+    pub fn finish(&self) -> DbResult<()> {
+        if self.position == self.data.len() {
+            Ok(())
+        } else {
+            Err(DbError::StorageError("Unexpected trailing data".into()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::commands::Operator;
+    use std::collections::BTreeSet;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("rust_database_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn sample_database() -> Database<String> {
+        let mut db = Database::new();
+        let columns = vec![
+            ColumnDef::new("id", DataType::String),
+            ColumnDef::new("year", DataType::Int).not_null(),
+            ColumnDef::new("rating", DataType::Float).default(Value::Float(3.0)),
+            ColumnDef::new("is_foundational", DataType::Bool),
+        ];
+        let mut table = Table::new("library".into(), columns, vec!["id".into()]);
+        for (id, year) in [("lib1", 2013), ("lib2", 2018)] {
+            let fields = HashMap::from([
+                ("id".to_string(), Value::String(id.into())),
+                ("year".to_string(), Value::Int(year)),
+                ("rating".to_string(), Value::Float(4.5)),
+                ("is_foundational".to_string(), Value::Bool(year == 2013)),
+            ]);
+            if let Err(e) = table.insert(Record { fields }) {
+                panic!("Not able to insert a record: {:?}", e);
+            }
+        }
+        if let Err(e) = table.create_index("library_year".into(), "year".into()) {
+            panic!("Not able to create an index: {:?}", e);
+        }
+        if let Err(e) = db.create_table(table) {
+            panic!("Not able to create a table: {:?}", e);
+        }
+        db
+    }
+
+    #[test]
+    fn test_flush_and_open_round_trip() {
+        let dir = temp_dir("round_trip");
+        if let Err(e) = sample_database().flush(&dir) {
+            panic!("Flush failed: {:?}", e);
+        }
+
+        match Database::<String>::open(&dir) {
+            Ok(db) => match db.get_table("library") {
+                Ok(table) => {
+                    assert_eq!(table.columns(), ["id", "year", "rating", "is_foundational"]);
+                    assert_eq!(table.pk_columns(), ["id"]);
+                    let nullable: Vec<bool> = table.column_defs().iter().map(|c| c.nullable).collect();
+                    assert_eq!(nullable, [false, false, true, true]);
+                    assert_eq!(table.column_defs()[2].default, Some(Value::Float(3.0)));
+                    assert_eq!(table.store.len(), 2);
+                    assert_eq!(table.store["lib1"].fields.get("is_foundational"), Some(&Value::Bool(true)));
+                    assert_eq!(table.store["lib2"].fields.get("year"), Some(&Value::Int(2018)));
+                    let lookup = table.index_on("year")
+                        .and_then(|index| index.lookup(&Operator::GreaterThan, &Value::Int(2015)));
+                    assert_eq!(lookup, Some(BTreeSet::from(["lib2".to_string()])));
+                }
+                Err(e) => panic!("Table missing after reopening: {:?}", e),
+            },
+            Err(e) => panic!("Open failed: {:?}", e),
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_open_detects_corruption() {
+        let dir = temp_dir("corruption");
+        if let Err(e) = sample_database().flush(&dir) {
+            panic!("Flush failed: {:?}", e);
+        }
+
+        let path = dir.join(table_file("library", 1));
+        let mut contents = fs::read(&path).unwrap_or_default();
+        let middle = contents.len() / 2;
+        contents[middle] ^= 0xFF;
+        if let Err(e) = fs::write(&path, contents) {
+            panic!("Not able to corrupt the file: {}", e);
+        }
+
+        match Database::<String>::open(&dir) {
+            Err(DbError::StorageError(_)) => {}
+            Ok(_) => panic!("Corrupted data should not load"),
+            Err(e) => panic!("StorageError expected, got: {:?}", e),
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_unfinished_snapshot_keeps_previous_one() {
+        let dir = temp_dir("unfinished");
+        let mut db = sample_database();
+        if let Err(e) = db.flush(&dir) {
+            panic!("Flush failed: {:?}", e);
+        }
+
+        // A crash after the table files of the next snapshot were written, but before its
+        // catalog replaced the old one.
+        if let Ok(table) = db.get_table_mut("library") {
+            table.delete(&"lib1".to_string());
+        }
+        if let Ok(table) = db.get_table("library")
+            && let Err(e) = write_file(&dir.join(table_file("library", 2)), TABLE_MAGIC, &encode_rows(table)) {
+            panic!("Not able to write a table file: {}", e);
+        }
+        match Database::<String>::open(&dir) {
+            Ok(reopened) => match reopened.get_table("library") {
+                Ok(table) => assert_eq!(table.store.len(), 2),
+                Err(e) => panic!("Table missing after reopening: {:?}", e),
+            },
+            Err(e) => panic!("Open failed: {:?}", e),
+        }
+
+        if let Err(e) = db.flush(&dir) {
+            panic!("Flush failed: {:?}", e);
+        }
+        assert!(!dir.join(table_file("library", 1)).exists(), "Old snapshot was not removed");
+        match Database::<String>::open(&dir) {
+            Ok(reopened) => match reopened.get_table("library") {
+                Ok(table) => assert_eq!(table.store.len(), 1),
+                Err(e) => panic!("Table missing after reopening: {:?}", e),
+            },
+            Err(e) => panic!("Open failed: {:?}", e),
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_open_empty_directory() {
+        let dir = temp_dir("empty");
+        match Database::<i64>::open(&dir) {
+            Ok(db) => assert!(db.get_table("library").is_err()),
+            Err(e) => panic!("Opening a missing directory failed: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_crc32_known_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+}
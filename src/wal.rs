@@ -0,0 +1,659 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use crate::domain::{ColumnDef, Record, Value};
+use crate::error::{DbError, DbResult};
+use crate::storage::{self, Decoder, Encoder};
+
+// The log starts with a magic number and a version. Each committed statement is appended as
+// one frame: payload length (u32), CRC-32 of the payload (u32), CRC-32 of these two fields
+// (u32) and the payload, which holds the log sequence number (u64) and the records of the
+// statement. A frame that was only partly written before a crash ends early or fails its
+// payload checksum at the end of the log and is cut off on recovery.
+// The version in the header is the storage format version the records are encoded with.
+const WAL_MAGIC: &[u8; 4] = b"RDBW";
+const HEADER_LEN: u64 = 6;
+const FRAME_HEADER_LEN: usize = 12;
+
+pub const WAL_FILE: &str = "wal.log";
+
+/// When appended frames are forced to disk with fsync.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncPolicy {
+    /// After every committed statement.
+    Always,
+    /// After every `n` committed statements.
+    Every(u32),
+    /// Never; the operating system decides when data reaches the disk.
+    Never,
+}
+
+/// A single change to the database, as stored in the log.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WalRecord {
+    CreateTable {
+        name: String,
+        pk_columns: Vec<String>,
+        columns: Vec<ColumnDef>,
+    },
+    Insert {
+        table: String,
+        record: Record,
+    },
+    Delete {
+        table: String,
+        key: Vec<Value>,
+    },
+    CreateIndex {
+        table: String,
+        name: String,
+        column: String,
+    },
+    DropIndex {
+        table: String,
+        name: String,
+    },
+    DropTable {
+        name: String,
+    },
+    Truncate {
+        table: String,
+    },
+    AddColumn {
+        table: String,
+        column: ColumnDef,
+    },
+    DropColumn {
+        table: String,
+        column: String,
+    },
+    RenameColumn {
+        table: String,
+        from: String,
+        to: String,
+    },
+    RenameTable {
+        from: String,
+        to: String,
+    },
+}
+
+pub struct Wal {
+    dir: PathBuf,
+    file: File,
+    policy: SyncPolicy,
+    last_lsn: u64,
+    unsynced: u32,
+}
+
+impl Wal {
+    /// Opens the log in `dir` for appending and returns the records of every frame newer
+    /// than `after_lsn`, the last sequence number already contained in the snapshot.
+    /// A torn frame at the end of the log is truncated away, while a damaged frame with more
+    /// data after it is reported as an error, as cutting it off would lose committed changes.
+    pub fn recover(dir: &Path, after_lsn: u64, policy: SyncPolicy) -> DbResult<(Wal, Vec<WalRecord>)> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(WAL_FILE);
+        let contents = if path.exists() { fs::read(&path)? } else { Vec::new() };
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        let mut wal = Wal {
+            dir: dir.to_path_buf(),
+            file,
+            policy,
+            last_lsn: after_lsn,
+            unsynced: 0,
+        };
+        if (contents.len() as u64) < HEADER_LEN {
+            wal.reset()?;
+            return Ok((wal, Vec::new()));
+        }
+        let version = u16::from_le_bytes([contents[4], contents[5]]);
+        if &contents[..4] != WAL_MAGIC || version != storage::FORMAT_VERSION {
+            return Err(DbError::StorageError(format!("{} is not a supported log file", path.display())));
+        }
+
+        let mut records = Vec::new();
+        let mut valid_len = HEADER_LEN as usize;
+        loop {
+            let (payload, end) = match next_frame(&contents, valid_len) {
+                Frame::Complete(payload, end) => (payload, end),
+                Frame::Torn => break,
+                Frame::Damaged => return Err(DbError::StorageError(format!(
+                    "{} is damaged at offset {}", path.display(), valid_len
+                ))),
+            };
+            let mut decoder = Decoder::new(payload);
+            let lsn = decoder.u64()?;
+            let count = decoder.u32()?;
+            let mut frame = Vec::new();
+            for _ in 0..count {
+                frame.push(decode_record(&mut decoder)?);
+            }
+            decoder.finish()?;
+            if lsn > after_lsn {
+                records.extend(frame);
+            }
+            wal.last_lsn = wal.last_lsn.max(lsn);
+            valid_len = end;
+        }
+        if valid_len < contents.len() {
+            wal.file.set_len(valid_len as u64)?;
+            wal.file.sync_all()?;
+        }
+        Ok((wal, records))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn last_lsn(&self) -> u64 {
+        self.last_lsn
+    }
+
+    /// Appends the records of one statement as a single frame.
+    pub fn append(&mut self, records: &[WalRecord]) -> DbResult<()> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        let lsn = self.last_lsn + 1;
+        let mut payload = Encoder::new();
+        payload.put_u64(lsn);
+        payload.put_u32(records.len() as u32);
+        for record in records {
+            encode_record(&mut payload, record);
+        }
+
+        let mut frame = Vec::with_capacity(payload.bytes.len() + FRAME_HEADER_LEN);
+        frame.extend_from_slice(&(payload.bytes.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&storage::crc32(&payload.bytes).to_le_bytes());
+        let header_checksum = storage::crc32(&frame);
+        frame.extend_from_slice(&header_checksum.to_le_bytes());
+        frame.extend_from_slice(&payload.bytes);
+        self.file.write_all(&frame)?;
+        self.last_lsn = lsn;
+
+        match self.policy {
+            SyncPolicy::Always => self.file.sync_data()?,
+            SyncPolicy::Every(n) => {
+                self.unsynced += 1;
+                if self.unsynced >= n {
+                    self.file.sync_data()?;
+                    self.unsynced = 0;
+                }
+            }
+            SyncPolicy::Never => {}
+        }
+        Ok(())
+    }
+
+    /// Drops every frame; called once a snapshot containing them has been written.
+    pub fn reset(&mut self) -> DbResult<()> {
+        self.file.set_len(0)?;
+        self.file.write_all(WAL_MAGIC)?;
+        self.file.write_all(&storage::FORMAT_VERSION.to_le_bytes())?;
+        self.file.sync_all()?;
+        self.unsynced = 0;
+        Ok(())
+    }
+}
+
+enum Frame<'a> {
+    /// The payload of an intact frame and the offset just past it.
+    Complete(&'a [u8], usize),
+    /// A frame with an intact header whose payload is cut short by the end of the log, or
+    /// fails its checksum in the last bytes of the log; what a crash in the middle of an
+    /// append leaves behind. An incomplete header at the end of the log counts as torn too.
+    Torn,
+    /// A frame whose header fails its checksum, or whose payload fails it with more data
+    /// after it.
+    Damaged,
+}
+
+fn next_frame(contents: &[u8], start: usize) -> Frame<'_> {
+    let Some(header) = contents.get(start..start + FRAME_HEADER_LEN) else {
+        return Frame::Torn;
+    };
+    if storage::crc32(&header[..8]) != u32::from_le_bytes([header[8], header[9], header[10], header[11]]) {
+        return Frame::Damaged;
+    }
+    let length = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let checksum = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    let end = match (start + FRAME_HEADER_LEN).checked_add(length) {
+        Some(end) if end <= contents.len() => end,
+        _ => return Frame::Torn,
+    };
+    let payload = &contents[start + FRAME_HEADER_LEN..end];
+    if storage::crc32(payload) == checksum {
+        Frame::Complete(payload, end)
+    } else if end == contents.len() {
+        Frame::Torn
+    } else {
+        Frame::Damaged
+    }
+}
+
+fn encode_record(encoder: &mut Encoder, record: &WalRecord) {
+    match record {
+        WalRecord::CreateTable { name, pk_columns, columns } => {
+            encoder.put_u8(0);
+            encoder.put_str(name);
+            encoder.put_key_columns(pk_columns);
+            encoder.put_u32(columns.len() as u32);
+            for column in columns {
+                encoder.put_column(column);
+            }
+        }
+        WalRecord::Insert { table, record } => {
+            encoder.put_u8(1);
+            encoder.put_str(table);
+            encoder.put_u32(record.fields.len() as u32);
+            for (column, value) in &record.fields {
+                encoder.put_str(column);
+                encoder.put_value(value);
+            }
+        }
+        WalRecord::Delete { table, key } => {
+            encoder.put_u8(2);
+            encoder.put_str(table);
+            encoder.put_u32(key.len() as u32);
+            for value in key {
+                encoder.put_value(value);
+            }
+        }
+        WalRecord::CreateIndex { table, name, column } => {
+            encoder.put_u8(3);
+            encoder.put_str(table);
+            encoder.put_str(name);
+            encoder.put_str(column);
+        }
+        WalRecord::DropIndex { table, name } => {
+            encoder.put_u8(4);
+            encoder.put_str(table);
+            encoder.put_str(name);
+        }
+        WalRecord::DropTable { name } => {
+            encoder.put_u8(5);
+            encoder.put_str(name);
+        }
+        WalRecord::Truncate { table } => {
+            encoder.put_u8(6);
+            encoder.put_str(table);
+        }
+        WalRecord::AddColumn { table, column } => {
+            encoder.put_u8(7);
+            encoder.put_str(table);
+            encoder.put_column(column);
+        }
+        WalRecord::DropColumn { table, column } => {
+            encoder.put_u8(8);
+            encoder.put_str(table);
+            encoder.put_str(column);
+        }
+        WalRecord::RenameColumn { table, from, to } => {
+            encoder.put_u8(9);
+            encoder.put_str(table);
+            encoder.put_str(from);
+            encoder.put_str(to);
+        }
+        WalRecord::RenameTable { from, to } => {
+            encoder.put_u8(10);
+            encoder.put_str(from);
+            encoder.put_str(to);
+        }
+    }
+}
+
+fn decode_record(decoder: &mut Decoder) -> DbResult<WalRecord> {
+    match decoder.u8()? {
+        0 => {
+            let name = decoder.string()?;
+            let pk_columns = decoder.key_columns()?;
+            let count = decoder.u32()?;
+            let mut columns = Vec::new();
+            for _ in 0..count {
+                columns.push(decoder.column()?);
+            }
+            Ok(WalRecord::CreateTable { name, pk_columns, columns })
+        }
+        1 => {
+            let table = decoder.string()?;
+            let count = decoder.u32()?;
+            let mut fields = HashMap::new();
+            for _ in 0..count {
+                let column = decoder.string()?;
+                fields.insert(column, decoder.value()?);
+            }
+            Ok(WalRecord::Insert { table, record: Record { fields } })
+        }
+        2 => {
+            let table = decoder.string()?;
+            let count = decoder.u32()?;
+            let mut key = Vec::new();
+            for _ in 0..count {
+                key.push(decoder.value()?);
+            }
+            Ok(WalRecord::Delete { table, key })
+        }
+        3 => Ok(WalRecord::CreateIndex { table: decoder.string()?, name: decoder.string()?, column: decoder.string()? }),
+        4 => Ok(WalRecord::DropIndex { table: decoder.string()?, name: decoder.string()? }),
+        5 => Ok(WalRecord::DropTable { name: decoder.string()? }),
+        6 => Ok(WalRecord::Truncate { table: decoder.string()? }),
+        7 => Ok(WalRecord::AddColumn { table: decoder.string()?, column: decoder.column()? }),
+        8 => Ok(WalRecord::DropColumn { table: decoder.string()?, column: decoder.string()? }),
+        9 => Ok(WalRecord::RenameColumn { table: decoder.string()?, from: decoder.string()?, to: decoder.string()? }),
+        10 => Ok(WalRecord::RenameTable { from: decoder.string()?, to: decoder.string()? }),
+        tag => Err(DbError::StorageError(format!("Unknown log record tag {}", tag))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::domain::{DatabaseKey, Key};
+    use crate::queries::parse;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rust_database_wal_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn run<K: DatabaseKey>(db: &mut Database<K>, input: &str) {
+        let query = match parse(input) {
+            Ok(query) => query,
+            Err(e) => panic!("Parsing '{}' failed: {:?}", input, e),
+        };
+        if let Err(e) = db.execute(query) {
+            panic!("Executing '{}' failed: {:?}", input, e);
+        }
+    }
+
+    fn insert(id: i64) -> WalRecord {
+        WalRecord::Insert {
+            table: "t".into(),
+            record: Record { fields: HashMap::from([("id".to_string(), Value::Int(id))]) },
+        }
+    }
+
+    #[test]
+    fn test_recover_replays_log_after_crash() {
+        let dir = temp_dir("replay");
+        {
+            let mut db = match Database::<i64>::open(&dir) {
+                Ok(db) => db,
+                Err(e) => panic!("Open failed: {:?}", e),
+            };
+            run(&mut db, "CREATE people KEY id FIELDS id:Int, job:String");
+            run(&mut db, "INSERT id=1, job=\"actor\" INTO people");
+            run(&mut db, "INSERT id=2, job=\"baker\" INTO people");
+            run(&mut db, "UPDATE people SET job = \"cook\" WHERE id = 2");
+            run(&mut db, "DELETE 1 FROM people");
+            // Dropped without a checkpoint, as if the process had crashed.
+        }
+
+        match Database::<i64>::open(&dir) {
+            Ok(db) => match db.get_table("people") {
+                Ok(table) => {
+                    assert_eq!(table.store.len(), 1);
+                    assert_eq!(table.store[&2].fields.get("job"), Some(&Value::String("cook".into())));
+                }
+                Err(e) => panic!("Table missing after recovery: {:?}", e),
+            },
+            Err(e) => panic!("Recovery failed: {:?}", e),
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_checkpoint_skips_records_in_snapshot() {
+        let dir = temp_dir("checkpoint");
+        {
+            let mut db = match Database::<i64>::open(&dir) {
+                Ok(db) => db,
+                Err(e) => panic!("Open failed: {:?}", e),
+            };
+            run(&mut db, "CREATE people KEY id FIELDS id:Int");
+            run(&mut db, "INSERT id=1 INTO people");
+            // A snapshot written without resetting the log must not apply records twice.
+            if let Err(e) = db.flush(&dir) {
+                panic!("Flush failed: {:?}", e);
+            }
+            run(&mut db, "INSERT id=2 INTO people");
+        }
+
+        match Database::<i64>::open(&dir) {
+            Ok(db) => match db.get_table("people") {
+                Ok(table) => assert_eq!(table.store.keys().copied().collect::<Vec<_>>(), vec![1, 2]),
+                Err(e) => panic!("Table missing after recovery: {:?}", e),
+            },
+            Err(e) => panic!("Recovery failed: {:?}", e),
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_flush_is_rejected_inside_transaction() {
+        let dir = temp_dir("flush_transaction");
+        {
+            let mut db = match Database::<i64>::open(&dir) {
+                Ok(db) => db,
+                Err(e) => panic!("Open failed: {:?}", e),
+            };
+            run(&mut db, "CREATE t KEY id FIELDS id:Int");
+            run(&mut db, "BEGIN");
+            run(&mut db, "INSERT id=5 INTO t");
+            // The snapshot would hold row 5 under an older sequence number than its COMMIT.
+            assert!(matches!(db.flush(&dir), Err(DbError::InvalidCommand(_))));
+            run(&mut db, "COMMIT");
+        }
+
+        match Database::<i64>::open(&dir) {
+            Ok(db) => match db.get_table("t") {
+                Ok(table) => assert_eq!(table.store.keys().copied().collect::<Vec<_>>(), vec![5]),
+                Err(e) => panic!("Table missing after recovery: {:?}", e),
+            },
+            Err(e) => panic!("Recovery failed: {:?}", e),
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_recover_replays_schema_changes() {
+        let dir = temp_dir("schema");
+        {
+            let mut db = match Database::<i64>::open(&dir) {
+                Ok(db) => db,
+                Err(e) => panic!("Open failed: {:?}", e),
+            };
+            run(&mut db, "CREATE people KEY id FIELDS id:Int, job:String");
+            run(&mut db, "CREATE pets KEY id FIELDS id:Int");
+            run(&mut db, "INSERT id=1, job=\"actor\" INTO people");
+            run(&mut db, "ALTER TABLE people ADD age:Int DEFAULT 18");
+            run(&mut db, "ALTER TABLE people RENAME COLUMN job TO occupation");
+            run(&mut db, "ALTER TABLE people RENAME TO persons");
+            run(&mut db, "TRUNCATE pets");
+            run(&mut db, "DROP TABLE pets");
+        }
+
+        match Database::<i64>::open(&dir) {
+            Ok(db) => {
+                assert!(db.get_table("pets").is_err());
+                match db.get_table("persons") {
+                    Ok(table) => {
+                        assert_eq!(table.columns(), ["id", "occupation", "age"]);
+                        assert_eq!(table.store[&1].fields.get("age"), Some(&Value::Int(18)));
+                    }
+                    Err(e) => panic!("Table missing after recovery: {:?}", e),
+                }
+            }
+            Err(e) => panic!("Recovery failed: {:?}", e),
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_recover_replays_composite_keys() {
+        let dir = temp_dir("composite");
+        {
+            let mut db = match Database::<Key>::open(&dir) {
+                Ok(db) => db,
+                Err(e) => panic!("Open failed: {:?}", e),
+            };
+            run(&mut db, "CREATE visits KEY (person, day) FIELDS person:Int, day:String");
+            run(&mut db, "INSERT person=1, day=\"mon\" INTO visits");
+            run(&mut db, "INSERT person=1, day=\"tue\" INTO visits");
+            if let Err(e) = db.checkpoint() {
+                panic!("Checkpoint failed: {:?}", e);
+            }
+            run(&mut db, "INSERT person=2, day=\"mon\" INTO visits");
+            run(&mut db, "DELETE (1, \"mon\") FROM visits");
+        }
+
+        match Database::<Key>::open(&dir) {
+            Ok(db) => match db.get_table("visits") {
+                Ok(table) => {
+                    let key = |person: i64, day: &str| Key::Tuple(vec![Key::Int(person), Key::String(day.into())]);
+                    assert_eq!(table.pk_columns(), ["person", "day"]);
+                    assert_eq!(table.store.keys().cloned().collect::<Vec<_>>(), vec![key(1, "tue"), key(2, "mon")]);
+                }
+                Err(e) => panic!("Table missing after recovery: {:?}", e),
+            },
+            Err(e) => panic!("Recovery failed: {:?}", e),
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_recover_ignores_uncommitted_transaction() {
+        let dir = temp_dir("transaction");
+        {
+            let mut db = match Database::<i64>::open(&dir) {
+                Ok(db) => db,
+                Err(e) => panic!("Open failed: {:?}", e),
+            };
+            run(&mut db, "CREATE people KEY id FIELDS id:Int");
+            run(&mut db, "BEGIN");
+            run(&mut db, "INSERT id=1 INTO people");
+            run(&mut db, "COMMIT");
+            run(&mut db, "BEGIN");
+            run(&mut db, "INSERT id=2 INTO people");
+        }
+
+        match Database::<i64>::open(&dir) {
+            Ok(db) => match db.get_table("people") {
+                Ok(table) => assert_eq!(table.store.keys().copied().collect::<Vec<_>>(), vec![1]),
+                Err(e) => panic!("Table missing after recovery: {:?}", e),
+            },
+            Err(e) => panic!("Recovery failed: {:?}", e),
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_recover_truncates_torn_tail() {
+        let dir = temp_dir("torn");
+        let full_len = match Wal::recover(&dir, 0, SyncPolicy::Always) {
+            Ok((mut wal, _)) => {
+                for id in [1, 2] {
+                    if let Err(e) = wal.append(&[insert(id)]) {
+                        panic!("Append failed: {:?}", e);
+                    }
+                }
+                fs::metadata(dir.join(WAL_FILE)).map(|m| m.len()).unwrap_or(0)
+            }
+            Err(e) => panic!("Opening the log failed: {:?}", e),
+        };
+
+        // Cut the second frame in half, as a crash during the write would.
+        let contents = fs::read(dir.join(WAL_FILE)).unwrap_or_default();
+        if let Err(e) = fs::write(dir.join(WAL_FILE), &contents[..contents.len() - 5]) {
+            panic!("Not able to tear the log: {}", e);
+        }
+
+        match Wal::recover(&dir, 0, SyncPolicy::Always) {
+            Ok((wal, records)) => {
+                assert_eq!(records, vec![insert(1)]);
+                assert_eq!(wal.last_lsn(), 1);
+                let len = fs::metadata(dir.join(WAL_FILE)).map(|m| m.len()).unwrap_or(0);
+                assert!(len < full_len - 5, "Torn frame was not truncated");
+            }
+            Err(e) => panic!("Recovery failed: {:?}", e),
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_recover_rejects_damaged_frame_before_valid_ones() {
+        let dir = temp_dir("damaged");
+        let frame_len = match Wal::recover(&dir, 0, SyncPolicy::Always) {
+            Ok((mut wal, _)) => {
+                let mut lengths = Vec::new();
+                for id in [1, 2, 3] {
+                    if let Err(e) = wal.append(&[insert(id)]) {
+                        panic!("Append failed: {:?}", e);
+                    }
+                    lengths.push(fs::metadata(dir.join(WAL_FILE)).map(|m| m.len()).unwrap_or(0));
+                }
+                (lengths[1] - lengths[0]) as usize
+            }
+            Err(e) => panic!("Opening the log failed: {:?}", e),
+        };
+
+        // Flip the last byte of the second frame, leaving the third one intact after it.
+        let mut contents = fs::read(dir.join(WAL_FILE)).unwrap_or_default();
+        let position = contents.len() - frame_len - 1;
+        contents[position] ^= 0xFF;
+        if let Err(e) = fs::write(dir.join(WAL_FILE), &contents) {
+            panic!("Not able to damage the log: {}", e);
+        }
+
+        match Wal::recover(&dir, 0, SyncPolicy::Always) {
+            Err(DbError::StorageError(_)) => {}
+            other => panic!("StorageError expected, got: {:?}", other.map(|(_, records)| records)),
+        }
+        assert_eq!(fs::read(dir.join(WAL_FILE)).unwrap_or_default(), contents, "Damaged log was modified");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_recover_rejects_damaged_frame_length() {
+        let dir = temp_dir("damaged_length");
+        match Wal::recover(&dir, 0, SyncPolicy::Always) {
+            Ok((mut wal, _)) => {
+                for id in [1, 2, 3] {
+                    if let Err(e) = wal.append(&[insert(id)]) {
+                        panic!("Append failed: {:?}", e);
+                    }
+                }
+            }
+            Err(e) => panic!("Opening the log failed: {:?}", e),
+        }
+
+        // Make the length of the first frame point far past the end of the log.
+        let mut contents = fs::read(dir.join(WAL_FILE)).unwrap_or_default();
+        contents[HEADER_LEN as usize + 3] ^= 0x01;
+        if let Err(e) = fs::write(dir.join(WAL_FILE), &contents) {
+            panic!("Not able to damage the log: {}", e);
+        }
+
+        match Wal::recover(&dir, 0, SyncPolicy::Always) {
+            Err(DbError::StorageError(_)) => {}
+            other => panic!("StorageError expected, got: {:?}", other.map(|(_, records)| records)),
+        }
+        assert_eq!(fs::read(dir.join(WAL_FILE)).unwrap_or_default(), contents, "Damaged log was modified");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}